@@ -0,0 +1,288 @@
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use crate::configuration::{BackendKind, RootConfig};
+use crate::dropbox::Dropbox;
+use crate::dropbox_info::{self, Account};
+use crate::error::Error;
+use crate::ignore_list::IgnoreList;
+use crate::maestral::Maestral;
+use crate::nextcloud::Nextcloud;
+use crate::syncthing::Syncthing;
+
+/// A storage for the ignore state of file system entries.
+///
+/// Entries ignored through a backend are considered to be managed by dropignore, while entries
+/// which were ignored by other means are considered foreign until they get adopted via `manage`.
+pub trait IgnoreBackend {
+    fn is_ignored(&self, path: &Path) -> Result<bool, Error>;
+
+    fn ignore(&self, path: &Path) -> Result<(), Error>;
+
+    fn unignore(&self, path: &Path) -> Result<(), Error>;
+
+    fn is_managed(&self, path: &Path) -> Result<bool, Error>;
+
+    /// Marks an already ignored entry as managed. Returns `false` if the entry isn't ignored.
+    fn manage(&self, path: &Path) -> Result<bool, Error>;
+
+    /// Determines the ignore state including any inconsistencies left behind on disk.
+    fn ignore_state(&self, path: &Path) -> Result<IgnoreState, Error> {
+        Ok(match self.is_ignored(path)? {
+            true => IgnoreState::Ignored,
+            false => IgnoreState::NotIgnored,
+        })
+    }
+
+    /// Rewrites an inconsistent ignore state into the one given by `IgnoreState::repaired`.
+    fn repair(&self, _path: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The ignore state of an entry as found in a backend.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IgnoreState {
+    NotIgnored,
+    Ignored,
+    /// only some of the ignore markers are present
+    Partial,
+    /// not ignored, but unexpected marker values or leftovers are present
+    Invalid,
+}
+
+impl IgnoreState {
+    /// The state a repair leaves the entry in: partially ignored entries get ignored completely,
+    /// invalid ones get unignored.
+    pub fn repaired(self) -> Self {
+        match self {
+            IgnoreState::Partial => IgnoreState::Ignored,
+            IgnoreState::Invalid => IgnoreState::NotIgnored,
+            state => state,
+        }
+    }
+}
+
+/// Creates the backend configured for the root containing the given path (default: Dropbox).
+pub fn for_path(
+    root_configs: &Option<Vec<RootConfig>>,
+    path: &Path,
+) -> Result<Box<dyn IgnoreBackend>, Error> {
+    let (root_path, backend_kind) = match configured_root(root_configs, path) {
+        Some(r) => r,
+        None => return Ok(Box::new(Dropbox::new()?)),
+    };
+
+    debug!("Using {:?} backend for {:?}", backend_kind, root_path);
+
+    let backend: Box<dyn IgnoreBackend> = match backend_kind {
+        BackendKind::Dropbox => Box::new(Dropbox::new()?),
+        BackendKind::Maestral => Box::new(IgnoreList::<Maestral>::new(&root_path)),
+        BackendKind::Nextcloud => Box::new(IgnoreList::<Nextcloud>::new(&root_path)),
+        BackendKind::Syncthing => Box::new(IgnoreList::<Syncthing>::new(&root_path)),
+    };
+
+    Ok(backend)
+}
+
+/// Returns the root folder containing the given path, either a configured one or a Dropbox
+/// root folder.
+pub fn root_for_path(root_configs: &Option<Vec<RootConfig>>, path: &Path) -> Option<PathBuf> {
+    if let Some((root_path, _)) = configured_root(root_configs, path) {
+        return Some(root_path);
+    }
+
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let info_file_path = dropbox_info::info_file_path()?;
+
+    dropbox_info::roots(&info_file_path, Account::All)
+        .ok()?
+        .into_iter()
+        .filter_map(|root_path| root_path.canonicalize().ok())
+        .find(|root_path| path.starts_with(root_path))
+}
+
+/// Returns the innermost configured root containing the given path along with its backend.
+fn configured_root(
+    root_configs: &Option<Vec<RootConfig>>,
+    path: &Path,
+) -> Option<(PathBuf, BackendKind)> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    root_configs
+        .iter()
+        .flatten()
+        .filter_map(|rc| {
+            let root_path = rc.path.canonicalize().ok()?;
+            Some((root_path, rc.backend))
+        })
+        .filter(|(root_path, _)| path.starts_with(root_path))
+        .max_by_key(|(root_path, _)| root_path.components().count())
+}
+
+#[cfg(test)]
+pub use memory::MemoryBackend;
+
+#[cfg(test)]
+mod memory {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    use super::{IgnoreBackend, IgnoreState};
+    use crate::error::Error;
+
+    /// Keeps the ignore state in memory only. Clones share the same state.
+    #[derive(Clone, Default)]
+    pub struct MemoryBackend {
+        // ignored entries mapped to their managed state
+        entries: Rc<RefCell<HashMap<PathBuf, bool>>>,
+        // entries in a state needing a repair
+        inconsistent: Rc<RefCell<HashMap<PathBuf, IgnoreState>>>,
+    }
+
+    impl MemoryBackend {
+        pub fn with_foreign_ignores(paths: &[&Path]) -> Self {
+            let backend = Self::default();
+            for path in paths {
+                backend
+                    .entries
+                    .borrow_mut()
+                    .insert(path.to_path_buf(), false);
+            }
+            backend
+        }
+
+        pub fn with_inconsistent_states(states: &[(&Path, IgnoreState)]) -> Self {
+            let backend = Self::default();
+            for (path, state) in states {
+                backend
+                    .inconsistent
+                    .borrow_mut()
+                    .insert(path.to_path_buf(), *state);
+            }
+            backend
+        }
+    }
+
+    impl IgnoreBackend for MemoryBackend {
+        fn is_ignored(&self, path: &Path) -> Result<bool, Error> {
+            Ok(self.entries.borrow().contains_key(path))
+        }
+
+        fn ignore(&self, path: &Path) -> Result<(), Error> {
+            self.entries.borrow_mut().insert(path.to_path_buf(), true);
+            Ok(())
+        }
+
+        fn unignore(&self, path: &Path) -> Result<(), Error> {
+            self.entries.borrow_mut().remove(path);
+            Ok(())
+        }
+
+        fn is_managed(&self, path: &Path) -> Result<bool, Error> {
+            Ok(self.entries.borrow().get(path).copied().unwrap_or(false))
+        }
+
+        fn manage(&self, path: &Path) -> Result<bool, Error> {
+            match self.entries.borrow_mut().get_mut(path) {
+                Some(managed) => {
+                    *managed = true;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+
+        fn ignore_state(&self, path: &Path) -> Result<IgnoreState, Error> {
+            if let Some(state) = self.inconsistent.borrow().get(path) {
+                return Ok(*state);
+            }
+
+            Ok(match self.is_ignored(path)? {
+                true => IgnoreState::Ignored,
+                false => IgnoreState::NotIgnored,
+            })
+        }
+
+        fn repair(&self, path: &Path) -> Result<(), Error> {
+            let state = self.inconsistent.borrow_mut().remove(path);
+
+            match state.map(IgnoreState::repaired) {
+                Some(IgnoreState::Ignored) => self.ignore(path),
+                Some(_) => self.unignore(path),
+                None => Ok(()),
+            }
+        }
+    }
+
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn ignore_and_unignore() {
+            let backend = MemoryBackend::default();
+            let path = Path::new("/foo/build");
+
+            assert!(!backend.is_ignored(path).unwrap());
+
+            backend.ignore(path).unwrap();
+
+            assert!(backend.is_ignored(path).unwrap());
+            assert!(backend.is_managed(path).unwrap());
+
+            backend.unignore(path).unwrap();
+
+            assert!(!backend.is_ignored(path).unwrap());
+            assert!(!backend.is_managed(path).unwrap());
+        }
+
+        #[test]
+        fn manage_foreign_ignore() {
+            let path = Path::new("/foo/build");
+            let backend = MemoryBackend::with_foreign_ignores(&[path]);
+
+            assert!(backend.is_ignored(path).unwrap());
+            assert!(!backend.is_managed(path).unwrap());
+
+            assert!(backend.manage(path).unwrap());
+
+            assert!(backend.is_managed(path).unwrap());
+            assert!(!backend.manage(Path::new("/foo/target")).unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn for_path_selects_innermost_root() {
+        let temp = TempDir::new().unwrap();
+        let outer = temp.path().canonicalize().unwrap().join("outer");
+        let inner = outer.join("inner");
+        fs::create_dir_all(inner.join("foo")).unwrap();
+
+        let root_configs = Some(vec![
+            RootConfig {
+                path: outer.clone(),
+                backend: BackendKind::Dropbox,
+            },
+            RootConfig {
+                path: inner.clone(),
+                backend: BackendKind::Syncthing,
+            },
+        ]);
+
+        let backend = for_path(&root_configs, &inner.join("foo")).unwrap();
+
+        backend.ignore(&inner.join("foo")).unwrap();
+
+        assert!(inner.join(".stignore").exists());
+    }
+}
@@ -0,0 +1,536 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use globset::GlobBuilder;
+
+use crate::configuration::{EntryType, MatcherConfig, MatcherSpec, RuleAction};
+use crate::matcher::{self, Candidate, MatchResult, Matcher, SpecRef};
+
+const GLOB_META_CHARS: &[char] = &['*', '?', '[', '{', '\\'];
+
+#[derive(Debug, PartialEq)]
+pub enum Severity {
+    /// keeps the configuration from working as intended
+    Error,
+    /// a spec without any effect
+    Warning,
+}
+
+/// A problem found by checking the configuration.
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Finding {
+    fn error(message: String) -> Self {
+        Finding {
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    fn warning(message: String) -> Self {
+        Finding {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+}
+
+/// The specs of the global configuration, a preset or a scope.
+struct Layer<'a> {
+    // e.g. `scopes[0].` or `presets[rust].`, empty for the global specs
+    prefix: String,
+    // e.g. `/Clients`, empty for the global specs
+    path: String,
+    // e.g. `scopes[0] "Clients"`, empty for the global specs
+    label: String,
+    // whether the specs are evaluated as global ones, i.e. of the global configuration or a preset
+    global: bool,
+    ignore_specs: &'a [MatcherSpec],
+    skip_specs: &'a [MatcherSpec],
+    rules: &'a [MatcherSpec],
+}
+
+impl<'a> Layer<'a> {
+    fn spec_lists(&self) -> [(String, &'a [MatcherSpec]); 3] {
+        [
+            (format!("{}ignore-specs", self.prefix), self.ignore_specs),
+            (format!("{}skip-specs", self.prefix), self.skip_specs),
+            (format!("{}rules", self.prefix), self.rules),
+        ]
+    }
+
+    /// Whether the specs of the other layer are evaluated below the ones of this layer for the
+    /// entries this layer applies to.
+    fn is_within(&self, other: &Layer) -> bool {
+        other.path.is_empty()
+            || self.path == other.path
+            || self
+                .path
+                .strip_prefix(&other.path)
+                .map(|rest| rest.starts_with('/'))
+                .unwrap_or(false)
+    }
+}
+
+/// Checks the configuration for invalid specs, specs without any effect and specs matching the
+/// scanned directories themselves.
+pub fn check(matcher_config: &Option<MatcherConfig>, directories: &[PathBuf]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    match Matcher::new(matcher_config) {
+        Ok(matcher) => check_directories(&matcher, directories, &mut findings),
+        Err(e) => findings.push(Finding::error(e.to_string())),
+    }
+
+    if let Some(matcher_config) = matcher_config {
+        let layers = layers(matcher_config);
+        check_skip_specs(&layers, &mut findings);
+        check_redundant_specs(&layers, &mut findings);
+        check_dot_directories(&layers, &mut findings);
+    }
+
+    findings
+}
+
+fn layers(matcher_config: &MatcherConfig) -> Vec<Layer<'_>> {
+    let global = Layer {
+        prefix: String::new(),
+        path: String::new(),
+        label: String::new(),
+        global: true,
+        ignore_specs: matcher_config.ignore_specs.as_deref().unwrap_or_default(),
+        skip_specs: matcher_config.skip_specs.as_deref().unwrap_or_default(),
+        rules: matcher_config.rules.as_deref().unwrap_or_default(),
+    };
+    // presets apply like the global specs, but are referred to by their name
+    let presets = matcher_config.presets.iter().map(|preset| Layer {
+        prefix: format!("presets[{}].", preset.name),
+        path: String::new(),
+        label: format!("presets[{}]", preset.name),
+        global: true,
+        ignore_specs: preset.ignore_specs.as_deref().unwrap_or_default(),
+        skip_specs: preset.skip_specs.as_deref().unwrap_or_default(),
+        rules: &[],
+    });
+    let scopes = matcher_config
+        .scopes
+        .iter()
+        .flatten()
+        .enumerate()
+        .map(|(index, scope_config)| Layer {
+            prefix: format!("scopes[{}].", index),
+            path: matcher::scope_path(&scope_config.path),
+            label: format!("scopes[{}] {:?}", index, scope_config.path),
+            global: false,
+            ignore_specs: scope_config.ignore_specs.as_deref().unwrap_or_default(),
+            skip_specs: scope_config.skip_specs.as_deref().unwrap_or_default(),
+            rules: scope_config.rules.as_deref().unwrap_or_default(),
+        });
+
+    std::iter::once(global)
+        .chain(presets)
+        .chain(scopes)
+        .collect()
+}
+
+/// Walks start with the scanned directory itself, so any spec matching it ignores everything.
+fn check_directories(matcher: &Matcher, directories: &[PathBuf], findings: &mut Vec<Finding>) {
+    for directory in directories {
+        let directory = directory
+            .canonicalize()
+            .unwrap_or_else(|_| directory.clone());
+        let candidate = Candidate::new(&directory, &directory).with_entry_type(EntryType::Dir);
+        let report = matcher.report(&candidate);
+
+        if report.result == MatchResult::Ignore {
+            for spec in report.ignore_specs {
+                findings.push(Finding::error(format!(
+                    "{} matches the scanned directory {:?} itself",
+                    spec, directory
+                )));
+            }
+        }
+    }
+}
+
+/// A skip-spec only takes effect for entries matching an ignore-spec of its own or an enclosing
+/// layer (or an ignoring rule there).
+fn check_skip_specs(layers: &[Layer], findings: &mut Vec<Finding>) {
+    for layer in layers {
+        let ignoring_specs: Vec<&MatcherSpec> = layers
+            .iter()
+            .filter(|other| layer.is_within(other))
+            .flat_map(|other| {
+                other.ignore_specs.iter().chain(
+                    other
+                        .rules
+                        .iter()
+                        .filter(|r| r.action == RuleAction::Ignore && !r.pattern.starts_with('!')),
+                )
+            })
+            .collect();
+        let specs = format!("{}skip-specs", layer.prefix);
+
+        for (index, skip_spec) in layer.skip_specs.iter().enumerate() {
+            if !ignoring_specs.iter().any(|i| may_overlap(skip_spec, i)) {
+                findings.push(Finding::warning(format!(
+                    "{} can never override any ignore-spec",
+                    spec_ref(&specs, index, skip_spec)
+                )));
+            }
+        }
+    }
+}
+
+/// Rules are left out, as their order matters. The specs of presets are compared with the
+/// global ones, as they are evaluated along with them.
+fn check_redundant_specs(layers: &[Layer], findings: &mut Vec<Finding>) {
+    let global: Vec<&Layer> = layers.iter().filter(|l| l.global).collect();
+    let groups =
+        std::iter::once(global).chain(layers.iter().filter(|l| !l.global).map(|l| vec![l]));
+
+    for group in groups {
+        for list in 0..2 {
+            let spec_list: Vec<(String, usize, &MatcherSpec)> = group
+                .iter()
+                .flat_map(|layer| {
+                    let (specs, spec_list) = layer.spec_lists()[list].clone();
+                    spec_list
+                        .iter()
+                        .enumerate()
+                        .map(move |(index, spec)| (specs.clone(), index, spec))
+                })
+                .collect();
+
+            check_redundant_list(&spec_list, findings);
+        }
+    }
+}
+
+/// Checks the specs of one kind, referred to by their list and their index there.
+fn check_redundant_list(spec_list: &[(String, usize, &MatcherSpec)], findings: &mut Vec<Finding>) {
+    for (position, (specs, index, spec)) in spec_list.iter().enumerate() {
+        let earlier = spec_list[..position].iter();
+        let later = spec_list.iter().skip(position + 1);
+
+        if let Some((other_specs, other_index, other)) = spec_list[..position]
+            .iter()
+            .find(|(_, _, other)| spec == other)
+        {
+            findings.push(Finding::warning(format!(
+                "{} duplicates {}",
+                spec_ref(specs, *index, spec),
+                spec_ref(other_specs, *other_index, other)
+            )));
+            continue;
+        }
+
+        if let Some((other_specs, other_index, other)) = earlier
+            .chain(later)
+            .find(|(_, _, other)| spec != other && is_subsumed(spec, other))
+        {
+            findings.push(Finding::warning(format!(
+                "{} is subsumed by {}",
+                spec_ref(specs, *index, spec),
+                spec_ref(other_specs, *other_index, other)
+            )));
+        }
+    }
+}
+
+/// Scans don't descend into dot-directories, so patterns for their contents only take effect
+/// while watching.
+fn check_dot_directories(layers: &[Layer], findings: &mut Vec<Finding>) {
+    for layer in layers {
+        if layer.path.split('/').any(is_dot_name) {
+            findings.push(Finding::warning(format!(
+                "{} lies within a dot-directory, which scans don't descend into",
+                layer.label
+            )));
+            continue;
+        }
+
+        for (specs, spec_list) in layer.spec_lists().iter() {
+            for (index, spec) in spec_list.iter().enumerate() {
+                if spec.regex().is_some() {
+                    continue;
+                }
+
+                let pattern = spec.pattern.strip_prefix('!').unwrap_or(&spec.pattern);
+                let mut components: Vec<&str> = pattern.trim_end_matches('/').split('/').collect();
+                components.pop();
+
+                if components.into_iter().any(is_dot_name) {
+                    findings.push(Finding::warning(format!(
+                        "{} targets the contents of a dot-directory, which scans don't descend into",
+                        spec_ref(specs, index, spec)
+                    )));
+                }
+            }
+        }
+    }
+}
+
+fn spec_ref<'a>(specs: &'a str, index: usize, spec: &'a MatcherSpec) -> SpecRef<'a> {
+    SpecRef {
+        specs,
+        index,
+        pattern: &spec.pattern,
+    }
+}
+
+fn is_dot_name(name: &str) -> bool {
+    name.starts_with('.') && name != "." && name != ".."
+}
+
+/// The name the spec matches at the end of a path, `None` if it may match any name or isn't a
+/// glob, e.g. `build` for `**/build` and `*.log` for `/logs/*.log`.
+fn name_glob(spec: &MatcherSpec) -> Option<&str> {
+    if spec.regex().is_some() || spec.pattern.is_empty() {
+        return None;
+    }
+
+    let pattern = spec.pattern.strip_prefix('!').unwrap_or(&spec.pattern);
+    match pattern.trim_end_matches('/').rsplit('/').next() {
+        Some("") | Some("**") | None => None,
+        Some(name) => Some(name),
+    }
+}
+
+/// Whether the name is matched by the glob, comparing them as they are if both are globs.
+fn name_matches(glob: &str, name: &str, case_insensitive: bool) -> bool {
+    if name.contains(GLOB_META_CHARS) {
+        return glob == name;
+    }
+
+    GlobBuilder::new(glob)
+        .case_insensitive(case_insensitive)
+        .literal_separator(true)
+        .build()
+        .map(|g| g.compile_matcher().is_match(name))
+        .unwrap_or(false)
+}
+
+/// Whether both specs may match the same entry, only ruling it out by their names or types.
+fn may_overlap(spec: &MatcherSpec, other: &MatcherSpec) -> bool {
+    if spec.entry_type != EntryType::Any
+        && other.entry_type != EntryType::Any
+        && spec.entry_type != other.entry_type
+    {
+        return false;
+    }
+
+    let case_insensitive = spec.case_insensitive || other.case_insensitive;
+    match (name_glob(spec), name_glob(other)) {
+        (Some(name), Some(other_name)) => {
+            if name.contains(GLOB_META_CHARS) && other_name.contains(GLOB_META_CHARS) {
+                return true;
+            }
+
+            name_matches(name, other_name, case_insensitive)
+                || name_matches(other_name, name, case_insensitive)
+        }
+        _ => true,
+    }
+}
+
+/// Whether the other spec matches every entry the spec matches, which is only decided for
+/// unrestricted specs of a name, e.g. `target` or `**/target` subsuming `/Projects/*/target`.
+fn is_subsumed(spec: &MatcherSpec, other: &MatcherSpec) -> bool {
+    if matcher::is_restricted(other) || other.regex().is_some() || spec.regex().is_some() {
+        return false;
+    }
+    if spec.case_insensitive && !other.case_insensitive {
+        return false;
+    }
+
+    let other_pattern = other.pattern.strip_prefix("**/").unwrap_or(&other.pattern);
+    if other_pattern.is_empty() || other_pattern.contains('/') {
+        return false;
+    }
+
+    name_glob(spec)
+        .map(|name| name_matches(other_pattern, name, other.case_insensitive))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::PresetConfig;
+    use crate::presets;
+    use config::{Config, FileFormat};
+    use rstest::rstest;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[rstest(
+        yaml,
+        expected,
+        case::valid(
+            "ignore-specs:\n  - pattern: build\n  - pattern: '**/src/target'\nskip-specs:\n  - pattern: '**/docs/build'\n",
+            vec![]
+        ),
+        case::invalid_pattern(
+            "ignore-specs:\n  - pattern: '**/src/bu{ild'\n",
+            vec!["Invalid pattern in ignore-specs[0]: error parsing glob '**/src/bu{ild': unclosed alternate group; missing '}' (maybe escape '{' with '[{]'?)"]
+        ),
+        case::skip_spec_without_ignore_spec(
+            "ignore-specs:\n  - pattern: target\nskip-specs:\n  - pattern: '**/docs/build'\n",
+            vec!["skip-specs[0] \"**/docs/build\" can never override any ignore-spec"]
+        ),
+        case::skip_spec_of_other_type(
+            "ignore-specs:\n  - pattern: build\n    type: dir\nskip-specs:\n  - pattern: build\n    type: file\n",
+            vec!["skip-specs[0] \"build\" can never override any ignore-spec"]
+        ),
+        case::skip_spec_of_glob(
+            "ignore-specs:\n  - pattern: '*.log'\nskip-specs:\n  - pattern: '**/keep.log'\n",
+            vec![]
+        ),
+        case::skip_spec_of_rule(
+            "rules:\n  - pattern: '**/build'\nskip-specs:\n  - pattern: build\n",
+            vec![]
+        ),
+        case::skip_spec_in_scope(
+            "ignore-specs:\n  - pattern: build\nscopes:\n  - path: Clients\n    skip-specs:\n      - pattern: build\n      - pattern: out\n",
+            vec!["scopes[0].skip-specs[1] \"out\" can never override any ignore-spec"]
+        ),
+        case::skip_spec_of_other_scope(
+            "scopes:\n  - path: Clients\n    ignore-specs:\n      - pattern: build\n  - path: Personal\n    skip-specs:\n      - pattern: build\n",
+            vec!["scopes[1].skip-specs[0] \"build\" can never override any ignore-spec"]
+        ),
+        case::duplicate(
+            "ignore-specs:\n  - pattern: build\n  - pattern: target\n  - pattern: build\n",
+            vec!["ignore-specs[2] \"build\" duplicates ignore-specs[0] \"build\""]
+        ),
+        case::duplicate_with_other_options(
+            "ignore-specs:\n  - pattern: build\n    type: dir\n  - pattern: build\n    type: file\n",
+            vec![]
+        ),
+        case::subsumed(
+            "ignore-specs:\n  - pattern: '/Projects/*/target'\n  - pattern: '**/target'\n  - pattern: '*.log'\n  - pattern: '**/logs/*.log'\n",
+            vec![
+                "ignore-specs[0] \"/Projects/*/target\" is subsumed by ignore-specs[1] \"**/target\"",
+                "ignore-specs[3] \"**/logs/*.log\" is subsumed by ignore-specs[2] \"*.log\""
+            ]
+        ),
+        case::subsumed_by_restricted(
+            "ignore-specs:\n  - pattern: target\n    when-sibling: Cargo.toml\n  - pattern: '**/src/target'\n",
+            vec![]
+        ),
+        case::dot_directory(
+            "ignore-specs:\n  - pattern: '**/.git/objects'\n  - pattern: .idea\n  - pattern: '/Projects/../build'\nrules:\n  - pattern: '!/.config/**'\n",
+            vec![
+                "ignore-specs[0] \"**/.git/objects\" targets the contents of a dot-directory, which scans don't descend into",
+                "rules[0] \"!/.config/**\" targets the contents of a dot-directory, which scans don't descend into"
+            ]
+        ),
+        case::dot_directory_scope(
+            "scopes:\n  - path: /.config\n    ignore-specs:\n      - pattern: cache\n",
+            vec!["scopes[0] \"/.config\" lies within a dot-directory, which scans don't descend into"]
+        )
+    )]
+    fn checks_specs(yaml: &str, expected: Vec<&str>) {
+        let findings = check(&Some(matcher_config(yaml)), &[]);
+
+        assert_eq!(
+            expected,
+            findings
+                .iter()
+                .map(|f| f.message.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest(
+        yaml,
+        expected_specs,
+        case::file_name("ignore-specs:\n  - pattern: Dropbox\n", vec!["ignore-specs[0] \"Dropbox\""]),
+        case::wildcard("ignore-specs:\n  - pattern: '*'\n", vec!["ignore-specs[0] \"*\""]),
+        case::rule("rules:\n  - pattern: '**/Dropbox'\n", vec!["rules[0] \"**/Dropbox\""]),
+        case::skipped(
+            "ignore-specs:\n  - pattern: '*'\nskip-specs:\n  - pattern: Dropbox\n",
+            vec![]
+        ),
+        case::anchored("ignore-specs:\n  - pattern: '/*'\n", vec!["ignore-specs[0] \"/*\""]),
+        case::below(
+            "ignore-specs:\n  - pattern: '/*/build'\n  - pattern: target\n",
+            vec![]
+        )
+    )]
+    fn checks_scanned_directories(yaml: &str, expected_specs: Vec<&str>) {
+        let temp = TempDir::new().unwrap();
+        let directory = temp.path().canonicalize().unwrap().join("Dropbox");
+        fs::create_dir(&directory).unwrap();
+
+        let findings = check(
+            &Some(matcher_config(yaml)),
+            std::slice::from_ref(&directory),
+        );
+
+        let expected: Vec<Finding> = expected_specs
+            .iter()
+            .map(|spec| {
+                Finding::error(format!(
+                    "{} matches the scanned directory {:?} itself",
+                    spec, directory
+                ))
+            })
+            .collect();
+        assert_eq!(expected, findings);
+    }
+
+    #[test]
+    fn names_presets_in_findings() {
+        let mut matcher_config = matcher_config("ignore-specs:\n  - pattern: '**/target'\n");
+        for name in &["rust", "maven"] {
+            let preset_config = presets::find(name).unwrap().matcher_config().unwrap();
+            matcher_config.presets.push(PresetConfig {
+                name: name.to_string(),
+                ignore_specs: preset_config.ignore_specs,
+                ..Default::default()
+            });
+        }
+
+        let findings = check(&Some(matcher_config), &[]);
+
+        assert_eq!(
+            vec![
+                "presets[rust].ignore-specs[0] \"target\" is subsumed by ignore-specs[0] \"**/target\"",
+                "presets[maven].ignore-specs[0] \"target\" is subsumed by ignore-specs[0] \"**/target\""
+            ],
+            findings
+                .iter()
+                .map(|f| f.message.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn presets_pass() {
+        for preset in presets::PRESETS {
+            let findings = check(&Some(preset.matcher_config().unwrap()), &[]);
+
+            assert_eq!(Vec::<Finding>::new(), findings, "{}", preset.name);
+        }
+    }
+
+    fn matcher_config(yaml: &str) -> MatcherConfig {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(yaml, FileFormat::Yaml))
+            .unwrap();
+
+        config.try_into().unwrap()
+    }
+}
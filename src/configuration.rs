@@ -1,47 +1,818 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use config::{Config, ConfigError, FileFormat};
 use directories::ProjectDirs;
 use log::debug;
-use serde::Deserialize;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+
+use crate::diagnostics::{self, Locations};
+use crate::error::Error;
+use crate::matcher::Matcher;
+use crate::presets;
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Configuration {
     #[serde(rename = "matcher")]
     pub matcher_config: Option<MatcherConfig>,
+    pub roots: Option<Vec<RootConfig>>,
+    pub gitignore: Option<Vec<GitignoreConfig>>,
+    /// built-in specs merged after the configured ones
+    pub presets: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct RootConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub backend: BackendKind,
+}
+
+/// A directory whose git work trees are scanned with their git ignore rules applied as well.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitignoreConfig {
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Dropbox,
+    Maestral,
+    Nextcloud,
+    Syncthing,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct MatcherConfig {
     pub ignore_specs: Option<Vec<MatcherSpec>>,
     pub skip_specs: Option<Vec<MatcherSpec>>,
+    /// evaluated in order on top of the specs above, the last matching rule wins
+    pub rules: Option<Vec<MatcherSpec>>,
+    pub scopes: Option<Vec<ScopeConfig>>,
+    /// normalize patterns and paths before matching, e.g. for decomposed names created on macOS
+    pub unicode_normalization: Option<Normalization>,
+    /// the specs of the configured presets, applied along with the ones above
+    #[serde(skip)]
+    pub presets: Vec<PresetConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+/// The specs of a preset, kept apart from the configured ones so they are referred to by their
+/// preset, e.g. `presets[rust].ignore-specs[0]`.
+#[derive(Clone, Debug, Default)]
+pub struct PresetConfig {
+    pub name: String,
+    pub ignore_specs: Option<Vec<MatcherSpec>>,
+    pub skip_specs: Option<Vec<MatcherSpec>>,
+}
+
+/// Specs only applying below a path relative to the root folder containing the scanned directory
+/// (e.g. the Dropbox folder), otherwise the scanned directory, on top of the global ones.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ScopeConfig {
+    pub path: String,
+    pub ignore_specs: Option<Vec<MatcherSpec>>,
+    pub skip_specs: Option<Vec<MatcherSpec>>,
+    pub rules: Option<Vec<MatcherSpec>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct MatcherSpec {
+    /// may be omitted for specs with metric predicates, matching any entry below the scanned
+    /// directory then
+    #[serde(default)]
     pub pattern: String,
+    #[serde(default)]
+    pub kind: PatternKind,
+    /// only supported by rules, where a leading `!` in the pattern also makes a rule skip
+    #[serde(default)]
+    pub action: RuleAction,
+    #[serde(rename = "type", default)]
+    pub entry_type: EntryType,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    /// the minimum depth of matching entries relative to the scanned directory (`1` = children)
+    pub min_depth: Option<usize>,
+    pub max_depth: Option<usize>,
+    /// only match if any of these markers exists next to the matching entry
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub when_sibling: Vec<String>,
+    /// only match if any of these markers exists in an ancestor within the scanned directory
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub when_parent_contains: Vec<String>,
+    /// only match if any of these markers exists inside the matching entry
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub when_contains: Vec<String>,
+    /// the minimum size of a file or the total size of a directory, e.g. `2 GB`
+    #[serde(default, deserialize_with = "size")]
+    pub min_size: Option<u64>,
+    /// the minimum number of entries within a directory (recursively)
+    pub min_entries: Option<u64>,
+    /// the minimum age of the latest modification within an entry, e.g. `30d`
+    #[serde(default, deserialize_with = "duration")]
+    pub older_than: Option<Duration>,
+    /// the maximum age of the latest modification within an entry, e.g. `12h`
+    #[serde(default, deserialize_with = "duration")]
+    pub newer_than: Option<Duration>,
+}
+
+const REGEX_PREFIX: &str = "regex:";
+const PATH_REGEX_PREFIX: &str = "path-regex:";
+
+impl MatcherSpec {
+    /// Returns the regular expression of a spec either of `kind: regex` or `kind: path-regex`, or
+    /// prefixed by `regex:` or `path-regex:`.
+    pub fn regex(&self) -> Option<&str> {
+        match self.kind {
+            PatternKind::Regex | PatternKind::PathRegex => Some(self.pattern.as_str()),
+            PatternKind::Glob => self
+                .pattern
+                .strip_prefix(REGEX_PREFIX)
+                .or_else(|| self.pattern.strip_prefix(PATH_REGEX_PREFIX)),
+        }
+    }
+
+    /// Whether the regular expression of the spec is matched against the relative path instead
+    /// of the name of the entry.
+    pub fn is_path_regex(&self) -> bool {
+        match self.kind {
+            PatternKind::PathRegex => true,
+            PatternKind::Regex => false,
+            PatternKind::Glob => self.pattern.starts_with(PATH_REGEX_PREFIX),
+        }
+    }
+
+    pub fn has_metric_predicates(&self) -> bool {
+        self.min_size.is_some()
+            || self.min_entries.is_some()
+            || self.older_than.is_some()
+            || self.newer_than.is_some()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    #[default]
+    Ignore,
+    Skip,
+}
+
+/// The Unicode normal forms, composed (NFC) or decomposed (NFD).
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    Nfc,
+    Nfd,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PatternKind {
+    #[default]
+    Glob,
+    /// a regular expression matched against the name of the entry
+    Regex,
+    /// a regular expression matched against the path relative to the scanned directory
+    PathRegex,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryType {
+    Dir,
+    File,
+    Symlink,
+    #[default]
+    Any,
+}
+
+/// Accepts a single value as well as a list of values.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrText {
+    Number(u64),
+    Text(String),
+}
+
+/// Accepts a number of bytes or a number followed by a unit (`B`, `KB`, `MB`, `GB` or `TB`,
+/// being powers of 1024).
+fn size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = match NumberOrText::deserialize(deserializer)? {
+        NumberOrText::Number(bytes) => return Ok(Some(bytes)),
+        NumberOrText::Text(text) => text,
+    };
+
+    let (value, unit) = split_unit(&text);
+    let factor: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(D::Error::custom(format!("invalid size unit in {:?}", text))),
+    };
+
+    value
+        .parse::<u64>()
+        .ok()
+        .and_then(|v| v.checked_mul(factor))
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("invalid size {:?}", text)))
+}
+
+/// Accepts a number of seconds or a number followed by a unit (`s`, `m`, `h`, `d` or `w`).
+fn duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = match NumberOrText::deserialize(deserializer)? {
+        NumberOrText::Number(seconds) => return Ok(Some(Duration::from_secs(seconds))),
+        NumberOrText::Text(text) => text,
+    };
+
+    let (value, unit) = split_unit(&text);
+    let factor: u64 = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(D::Error::custom(format!(
+                "invalid duration unit in {:?}",
+                text
+            )))
+        }
+    };
+
+    value
+        .parse::<u64>()
+        .ok()
+        .and_then(|v| v.checked_mul(factor))
+        .map(|seconds| Some(Duration::from_secs(seconds)))
+        .ok_or_else(|| D::Error::custom(format!("invalid duration {:?}", text)))
+}
+
+/// Locates the key in the configuration file.
+fn located(file: &Path, key: &str, reason: String) -> Error {
+    let position = fs::read_to_string(file)
+        .ok()
+        .and_then(|source| Locations::parse(&source).find(key));
+
+    Error::ConfigFile {
+        file: file.to_path_buf(),
+        key: key.to_string(),
+        position,
+        reason,
+    }
+}
+
+/// Describes a deserialization error. Unknown variants are reported like unknown fields, as the
+/// configuration library doesn't name the expected ones.
+fn describe(error: &ConfigError) -> String {
+    let reason = error.to_string();
+    let (name, variant) = match reason
+        .strip_prefix("enum ")
+        .and_then(|r| r.split_once(" does not have variant constructor "))
+    {
+        Some(unknown) => unknown,
+        None => return reason,
+    };
+
+    let variants = match name {
+        "BackendKind" => diagnostics::variants::<BackendKind>(),
+        "Normalization" => diagnostics::variants::<Normalization>(),
+        "RuleAction" => diagnostics::variants::<RuleAction>(),
+        "PatternKind" => diagnostics::variants::<PatternKind>(),
+        "EntryType" => diagnostics::variants::<EntryType>(),
+        _ => return reason,
+    };
+    let variants: Vec<String> = variants.iter().map(|v| format!("`{}`", v)).collect();
+
+    format!(
+        "unknown variant `{}`, expected one of {}",
+        variant,
+        variants.join(", ")
+    )
+}
+
+fn with_suggestion(reason: String) -> String {
+    match diagnostics::suggestion(&reason) {
+        Some(name) => format!("{}, did you mean `{}`?", reason, name),
+        None => reason,
+    }
+}
+
+fn split_unit(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    let index = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+
+    (&text[..index], text[index..].trim_start())
 }
 
 impl Configuration {
-    pub fn load(app_name: &str) -> Result<Configuration, ConfigError> {
-        let project_dirs = ProjectDirs::from("", "", app_name).unwrap();
-        let config_dir = project_dirs.config_dir();
+    pub fn load(app_name: &str) -> Result<Configuration, Error> {
+        let project_dirs = ProjectDirs::from("", "", app_name).ok_or_else(|| {
+            ConfigError::Message("Couldn't determine configuration directory".to_string())
+        })?;
+        let file = project_dirs
+            .config_dir()
+            .join(format!("{}{}", app_name, ".yml"));
+
+        Self::from_file(&file)
+    }
+
+    /// Loads the configuration file if it exists. Unknown keys and invalid specs are rejected,
+    /// pointing to their position in the file.
+    pub fn from_file(file: &Path) -> Result<Configuration, Error> {
         let mut config = Config::new();
 
-        if let Some(file_path) = config_dir.join(format!("{}{}", app_name, ".yml")).to_str() {
+        if let Some(file_path) = file.to_str() {
             debug!("Evaluating \"{}\"", file_path);
             config.merge(config::File::new(file_path, FileFormat::Yaml).required(false))?;
         }
 
-        config.try_into()
+        let mut configuration: Configuration =
+            serde_path_to_error::deserialize(config).map_err(|e| {
+                let reason = describe(e.inner());
+                located(file, &e.path().to_string(), with_suggestion(reason))
+            })?;
+
+        for (index, name) in configuration.presets.iter().flatten().enumerate() {
+            if presets::find(name).is_none() {
+                let names: Vec<String> = presets::PRESETS
+                    .iter()
+                    .map(|p| format!("`{}`", p.name))
+                    .collect();
+                let reason = format!(
+                    "unknown preset `{}`, expected one of {}",
+                    name,
+                    names.join(", ")
+                );
+                return Err(located(
+                    file,
+                    &format!("presets[{}]", index),
+                    with_suggestion(reason),
+                ));
+            }
+        }
+        configuration.apply_presets()?;
+
+        if let Err(e) = Matcher::new(&configuration.matcher_config) {
+            let key = match &e {
+                // located at the `presets` key, as the specs of presets aren't part of the file
+                Error::Pattern { specs, .. }
+                | Error::Regex { specs, .. }
+                | Error::Spec { specs, .. }
+                    if specs.starts_with("presets[") =>
+                {
+                    String::from("presets")
+                }
+                Error::Pattern { specs, index, .. } | Error::Regex { specs, index, .. } => {
+                    format!("matcher.{}[{}].pattern", specs, index)
+                }
+                Error::Spec { specs, index, .. } => format!("matcher.{}[{}]", specs, index),
+                _ => return Err(e),
+            };
+            return Err(located(file, &key, e.to_string()));
+        }
+
+        Ok(configuration)
+    }
+
+    /// Adds the specs of the configured presets as their own lists next to the configured ones.
+    fn apply_presets(&mut self) -> Result<(), Error> {
+        let names = match &self.presets {
+            Some(names) => names,
+            None => return Ok(()),
+        };
+
+        let matcher_config = self
+            .matcher_config
+            .get_or_insert_with(MatcherConfig::default);
+
+        for name in names {
+            let preset = presets::find(name).ok_or_else(|| {
+                ConfigError::Message(format!("Unknown preset {:?} in presets", name))
+            })?;
+            debug!("Applying preset \"{}\"", preset.name);
+
+            let preset_config = preset.matcher_config()?;
+            matcher_config.presets.push(PresetConfig {
+                name: preset.name.to_string(),
+                ignore_specs: preset_config.ignore_specs,
+                skip_specs: preset_config.skip_specs,
+            });
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
+    use tempfile::TempDir;
 
     #[test]
     fn instantiation() {
         Configuration::load("dropignore").unwrap();
     }
+
+    #[rstest(
+        yaml,
+        expected_key,
+        expected_position,
+        expected_reason,
+        case::unknown_key(
+            "matchers:\n  ignore-specs: []\n",
+            "matchers",
+            Some((1, 1)),
+            "did you mean `matcher`?"
+        ),
+        case::unknown_matcher_key(
+            "matcher:\n  ignore_specs:\n    - pattern: build\n",
+            "matcher.ignore_specs",
+            Some((2, 3)),
+            "unknown field `ignore_specs`, expected one of `ignore-specs`"
+        ),
+        case::unknown_spec_key(
+            "matcher:\n  ignore-specs:\n    - pattern: build\n    - patern: target\n",
+            "matcher.ignore-specs[1].patern",
+            Some((4, 7)),
+            "did you mean `pattern`?"
+        ),
+        case::unknown_variant(
+            "matcher:\n  ignore-specs:\n    - pattern: build\n      type: dri\n",
+            "matcher.ignore-specs[0].type",
+            Some((4, 7)),
+            "did you mean `dir`?"
+        ),
+        case::invalid_value(
+            "matcher:\n  ignore-specs:\n    - pattern: build\n      min-size: 2 XB\n",
+            "matcher.ignore-specs[0].min-size",
+            Some((4, 7)),
+            "invalid size unit in \"2 XB\""
+        ),
+        case::invalid_pattern(
+            "matcher:\n  ignore-specs:\n    - pattern: build\n    - pattern: '**/src/bu{ild'\n",
+            "matcher.ignore-specs[1].pattern",
+            Some((4, 7)),
+            "Invalid pattern in ignore-specs[1]: error parsing glob '**/src/bu{ild'"
+        ),
+        case::invalid_scope_pattern(
+            "matcher:\n  scopes:\n    - path: Clients\n      skip-specs:\n        - pattern: 'regex:(out'\n",
+            "matcher.scopes[0].skip-specs[0].pattern",
+            Some((5, 11)),
+            "Invalid regular expression in scopes[0].skip-specs[0]"
+        ),
+        case::missing_pattern(
+            "matcher:\n  rules:\n    - type: dir\n",
+            "matcher.rules[0]",
+            Some((3, 7)),
+            "pattern is required"
+        ),
+        case::unknown_preset(
+            "presets: [rust, nod]\n",
+            "presets[1]",
+            Some((1, 17)),
+            "unknown preset `nod`, expected one of `rust`"
+        )
+    )]
+    fn locates_invalid_configuration(
+        yaml: &str,
+        expected_key: &str,
+        expected_position: Option<(usize, usize)>,
+        expected_reason: &str,
+    ) {
+        let temp = TempDir::new().unwrap();
+        let file = temp.path().join("dropignore.yml");
+        fs::write(&file, yaml).unwrap();
+
+        match Configuration::from_file(&file) {
+            Err(Error::ConfigFile {
+                file: error_file,
+                key,
+                position,
+                reason,
+            }) => {
+                assert_eq!(file, error_file);
+                assert_eq!(expected_key, key);
+                assert_eq!(expected_position, position);
+                assert!(reason.contains(expected_reason), "{}", reason);
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        let temp = TempDir::new().unwrap();
+
+        let configuration = Configuration::from_file(&temp.path().join("dropignore.yml")).unwrap();
+
+        assert!(configuration.matcher_config.is_none());
+    }
+
+    #[test]
+    fn roots() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "roots:\n  - path: /foo\n    backend: syncthing\n  - path: /bar\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+        let roots = configuration.roots.unwrap();
+
+        assert_eq!(PathBuf::from("/foo"), roots[0].path);
+        assert_eq!(BackendKind::Syncthing, roots[0].backend);
+        assert_eq!(PathBuf::from("/bar"), roots[1].path);
+        assert_eq!(BackendKind::Dropbox, roots[1].backend);
+    }
+
+    #[test]
+    fn gitignore() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "gitignore:\n  - path: /foo/code\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+        let gitignore = configuration.gitignore.unwrap();
+
+        assert_eq!(PathBuf::from("/foo/code"), gitignore[0].path);
+    }
+
+    #[test]
+    fn rules() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "matcher:\n  rules:\n    - pattern: '**/build'\n    - pattern: '!**/docs/build'\n    - pattern: '**/vendor'\n      action: skip\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+        let rules = configuration.matcher_config.unwrap().rules.unwrap();
+
+        assert_eq!(RuleAction::Ignore, rules[0].action);
+        assert_eq!("!**/docs/build", rules[1].pattern);
+        assert_eq!(RuleAction::Skip, rules[2].action);
+    }
+
+    #[test]
+    fn scopes() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "matcher:\n  scopes:\n    - path: Clients\n      ignore-specs:\n        - pattern: '**/Renders'\n    - path: Personal\n      skip-specs:\n        - pattern: '**/Renders'\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+        let scopes = configuration.matcher_config.unwrap().scopes.unwrap();
+
+        assert_eq!("Clients", scopes[0].path);
+        assert_eq!(
+            "**/Renders",
+            scopes[0].ignore_specs.as_ref().unwrap()[0].pattern
+        );
+        assert!(scopes[1].ignore_specs.is_none());
+        assert_eq!(
+            "**/Renders",
+            scopes[1].skip_specs.as_ref().unwrap()[0].pattern
+        );
+    }
+
+    #[rstest(
+        value,
+        expected,
+        case::nfc("nfc", Some(Normalization::Nfc)),
+        case::nfd("nfd", Some(Normalization::Nfd))
+    )]
+    fn unicode_normalization(value: &str, expected: Option<Normalization>) {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                &format!("matcher:\n  unicode-normalization: {}\n", value),
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+
+        assert_eq!(
+            expected,
+            configuration.matcher_config.unwrap().unicode_normalization
+        );
+    }
+
+    #[test]
+    fn presets() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "matcher:\n  ignore-specs:\n    - pattern: '**/build'\npresets: [rust, maven]\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let mut configuration: Configuration = config.try_into().unwrap();
+        configuration.apply_presets().unwrap();
+        let matcher_config = configuration.matcher_config.unwrap();
+        let presets = &matcher_config.presets;
+
+        assert_eq!(1, matcher_config.ignore_specs.unwrap().len());
+        assert_eq!(2, presets.len());
+        assert_eq!("rust", presets[0].name);
+        assert_eq!(
+            vec!["Cargo.toml"],
+            presets[0].ignore_specs.as_ref().unwrap()[0].when_sibling
+        );
+        assert_eq!("maven", presets[1].name);
+        assert_eq!(
+            vec!["pom.xml"],
+            presets[1].ignore_specs.as_ref().unwrap()[0].when_sibling
+        );
+    }
+
+    #[test]
+    fn unknown_preset() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "presets: [rust, cobol]\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let mut configuration: Configuration = config.try_into().unwrap();
+        let message = configuration.apply_presets().err().unwrap().to_string();
+
+        assert!(message.contains("\"cobol\""));
+    }
+
+    #[test]
+    fn matcher_spec_options() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "matcher:\n  ignore-specs:\n    - pattern: build\n      type: dir\n      case-insensitive: true\n      min-depth: 2\n      max-depth: 4\n    - pattern: target\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+        let ignore_specs = configuration.matcher_config.unwrap().ignore_specs.unwrap();
+
+        assert_eq!(EntryType::Dir, ignore_specs[0].entry_type);
+        assert!(ignore_specs[0].case_insensitive);
+        assert_eq!(Some(2), ignore_specs[0].min_depth);
+        assert_eq!(Some(4), ignore_specs[0].max_depth);
+        assert_eq!(EntryType::Any, ignore_specs[1].entry_type);
+        assert!(!ignore_specs[1].case_insensitive);
+        assert_eq!(None, ignore_specs[1].min_depth);
+    }
+
+    #[test]
+    fn matcher_spec_conditions() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "matcher:\n  ignore-specs:\n    - pattern: target\n      when-sibling: [Cargo.toml, pom.xml]\n      when-parent-contains: package.json\n      when-contains: CACHEDIR.TAG\n    - pattern: build\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+        let ignore_specs = configuration.matcher_config.unwrap().ignore_specs.unwrap();
+
+        assert_eq!(vec!["Cargo.toml", "pom.xml"], ignore_specs[0].when_sibling);
+        assert_eq!(vec!["package.json"], ignore_specs[0].when_parent_contains);
+        assert_eq!(vec!["CACHEDIR.TAG"], ignore_specs[0].when_contains);
+        assert!(ignore_specs[1].when_sibling.is_empty());
+    }
+
+    #[test]
+    fn matcher_spec_regex() {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                "matcher:\n  ignore-specs:\n    - pattern: '^out\\d+$'\n      kind: regex\n    - pattern: 'regex:^tmp-.*'\n    - pattern: '*.log'\n    - pattern: '^/gen/'\n      kind: path-regex\n    - pattern: 'path-regex:^/out[^/]*$'\n",
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+        let ignore_specs = configuration.matcher_config.unwrap().ignore_specs.unwrap();
+
+        assert_eq!(Some("^out\\d+$"), ignore_specs[0].regex());
+        assert_eq!(Some("^tmp-.*"), ignore_specs[1].regex());
+        assert_eq!(None, ignore_specs[2].regex());
+        assert_eq!(Some("^/gen/"), ignore_specs[3].regex());
+        assert_eq!(Some("^/out[^/]*$"), ignore_specs[4].regex());
+        assert_eq!(
+            vec![false, false, false, true, true],
+            ignore_specs
+                .iter()
+                .map(MatcherSpec::is_path_regex)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest(
+        predicates,
+        expected_size,
+        expected_entries,
+        expected_older_than,
+        case::units("min-size: 2 GB\n      min-entries: 50000\n      older-than: 30d", Some(2 << 30), Some(50000), Some(30 * 24 * 60 * 60)),
+        case::numbers("min-size: 1024\n      older-than: 90", Some(1024), None, Some(90)),
+        case::short_units("min-size: 500m\n      older-than: 2w", Some(500 << 20), None, Some(14 * 24 * 60 * 60)),
+        case::none("type: dir", None, None, None)
+    )]
+    fn matcher_spec_metric_predicates(
+        predicates: &str,
+        expected_size: Option<u64>,
+        expected_entries: Option<u64>,
+        expected_older_than: Option<u64>,
+    ) {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                &format!("matcher:\n  ignore-specs:\n    - {}\n", predicates),
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        let configuration: Configuration = config.try_into().unwrap();
+        let ignore_specs = configuration.matcher_config.unwrap().ignore_specs.unwrap();
+
+        assert_eq!("", ignore_specs[0].pattern);
+        assert_eq!(expected_size, ignore_specs[0].min_size);
+        assert_eq!(expected_entries, ignore_specs[0].min_entries);
+        assert_eq!(
+            expected_older_than.map(Duration::from_secs),
+            ignore_specs[0].older_than
+        );
+    }
+
+    #[rstest(
+        predicate,
+        case::size_unit("min-size: 2 XB"),
+        case::size("min-size: lots"),
+        case::duration_unit("older-than: 3y"),
+        case::duration("newer-than: -1d")
+    )]
+    fn invalid_metric_predicates(predicate: &str) {
+        let mut config = Config::new();
+        config
+            .merge(config::File::from_str(
+                &format!("matcher:\n  ignore-specs:\n    - {}\n", predicate),
+                FileFormat::Yaml,
+            ))
+            .unwrap();
+
+        assert!(config.try_into::<Configuration>().is_err());
+    }
 }
@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+const CONFLICT_MARKER: &str = " (Selective Sync Conflict";
+
+/// Determines the original name of a copy Dropbox created due to a selective sync conflict,
+/// e.g. `target` for `target (Selective Sync Conflict)` or `a.txt` for
+/// `a (Selective Sync Conflict 2).txt`.
+pub fn original_name(file_name: &str) -> Option<String> {
+    let start = file_name
+        .rfind(CONFLICT_MARKER)
+        .filter(|start| *start > 0)?;
+    let rest = &file_name[start + CONFLICT_MARKER.len()..];
+    let end = rest.find(')')?;
+
+    // an optional counter distinguishes multiple conflicts of the same entry
+    let counter = rest[..end].trim_start();
+    if !counter.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let extension = &rest[end + 1..];
+    if !extension.is_empty() && (!extension.starts_with('.') || extension.contains(' ')) {
+        return None;
+    }
+
+    Some(format!("{}{}", &file_name[..start], extension))
+}
+
+/// Moves everything of the conflicting copy `from` into the original `to` which isn't present
+/// there yet. Identical files are dropped from the copy, the remaining copy is removed when it
+/// got empty.
+///
+/// Returns the entries of the copy which had to be kept as they differ from the original.
+pub fn merge(from: &Path, to: &Path, dry_run: bool) -> Result<Vec<PathBuf>, Error> {
+    if fs::symlink_metadata(to).is_err() {
+        if !dry_run {
+            fs::rename(from, to).map_err(|e| Error::io(from, e))?;
+        }
+        return Ok(vec![]);
+    }
+
+    let from_metadata = fs::symlink_metadata(from).map_err(|e| Error::io(from, e))?;
+    let to_metadata = fs::symlink_metadata(to).map_err(|e| Error::io(to, e))?;
+
+    if from_metadata.is_dir() && to_metadata.is_dir() {
+        let mut kept = vec![];
+        for entry in fs::read_dir(from).map_err(|e| Error::io(from, e))? {
+            let entry = entry.map_err(|e| Error::io(from, e))?;
+            kept.append(&mut merge(
+                &entry.path(),
+                &to.join(entry.file_name()),
+                dry_run,
+            )?);
+        }
+
+        if kept.is_empty() && !dry_run {
+            fs::remove_dir(from).map_err(|e| Error::io(from, e))?;
+        }
+        return Ok(kept);
+    }
+
+    if from_metadata.is_file() && to_metadata.is_file() && same_content(from, to)? {
+        if !dry_run {
+            fs::remove_file(from).map_err(|e| Error::io(from, e))?;
+        }
+        return Ok(vec![]);
+    }
+
+    Ok(vec![from.to_path_buf()])
+}
+
+/// Removes the conflicting copy entirely.
+pub fn delete(path: &Path) -> Result<(), Error> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| Error::io(path, e))?;
+
+    if metadata.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+    .map_err(|e| Error::io(path, e))
+}
+
+fn same_content(a: &Path, b: &Path) -> Result<bool, Error> {
+    let a_metadata = fs::metadata(a).map_err(|e| Error::io(a, e))?;
+    let b_metadata = fs::metadata(b).map_err(|e| Error::io(b, e))?;
+    if a_metadata.len() != b_metadata.len() {
+        return Ok(false);
+    }
+
+    let a_content = fs::read(a).map_err(|e| Error::io(a, e))?;
+    let b_content = fs::read(b).map_err(|e| Error::io(b, e))?;
+
+    Ok(a_content == b_content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::TempDir;
+
+    #[rstest(
+        file_name,
+        expected,
+        case::directory("target (Selective Sync Conflict)", Some("target")),
+        case::numbered("target (Selective Sync Conflict 2)", Some("target")),
+        case::file("a (Selective Sync Conflict).txt", Some("a.txt")),
+        case::with_spaces("my dir (Selective Sync Conflict)", Some("my dir")),
+        case::regular("target", None),
+        case::invalid_counter("target (Selective Sync Conflict x)", None),
+        case::unrelated_suffix("target (Selective Sync Conflict) old", None)
+    )]
+    fn original_name_of_conflict(file_name: &str, expected: Option<&str>) {
+        assert_eq!(expected.map(String::from), original_name(file_name));
+    }
+
+    #[test]
+    fn merge_moves_missing_entries() {
+        let temp = TempDir::new().unwrap();
+        let from = temp.path().join("src (Selective Sync Conflict)");
+        let to = temp.path().join("src");
+        arrange_file(&from.join("a/new.txt"), "new");
+        arrange_file(&from.join("same.txt"), "same");
+        arrange_file(&to.join("same.txt"), "same");
+
+        let kept = merge(&from, &to, false).unwrap();
+
+        assert!(kept.is_empty());
+        assert!(!from.exists());
+        assert_eq!("new", fs::read_to_string(to.join("a/new.txt")).unwrap());
+        assert_eq!("same", fs::read_to_string(to.join("same.txt")).unwrap());
+    }
+
+    #[test]
+    fn merge_keeps_differing_entries() {
+        let temp = TempDir::new().unwrap();
+        let from = temp.path().join("src (Selective Sync Conflict)");
+        let to = temp.path().join("src");
+        arrange_file(&from.join("changed.txt"), "theirs");
+        arrange_file(&to.join("changed.txt"), "mine");
+
+        let kept = merge(&from, &to, false).unwrap();
+
+        assert_eq!(vec![from.join("changed.txt")], kept);
+        assert_eq!("mine", fs::read_to_string(to.join("changed.txt")).unwrap());
+        assert!(from.join("changed.txt").exists());
+    }
+
+    #[test]
+    fn merge_renames_without_original() {
+        let temp = TempDir::new().unwrap();
+        let from = temp.path().join("src (Selective Sync Conflict)");
+        let to = temp.path().join("src");
+        arrange_file(&from.join("a.txt"), "a");
+
+        merge(&from, &to, false).unwrap();
+
+        assert!(!from.exists());
+        assert!(to.join("a.txt").exists());
+    }
+
+    #[test]
+    fn merge_with_dry_run_changes_nothing() {
+        let temp = TempDir::new().unwrap();
+        let from = temp.path().join("src (Selective Sync Conflict)");
+        let to = temp.path().join("src");
+        arrange_file(&from.join("new.txt"), "new");
+        fs::create_dir_all(&to).unwrap();
+
+        let kept = merge(&from, &to, true).unwrap();
+
+        assert!(kept.is_empty());
+        assert!(from.join("new.txt").exists());
+        assert!(!to.join("new.txt").exists());
+    }
+
+    #[test]
+    fn delete_removes_directory() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("target (Selective Sync Conflict)");
+        arrange_file(&path.join("classes/a.class"), "");
+
+        delete(&path).unwrap();
+
+        assert!(!path.exists());
+    }
+
+    fn arrange_file(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+/// The positions of the keys and sequence items of a YAML document by their path, e.g.
+/// `matcher.ignore-specs[0].pattern`.
+#[derive(Debug, Default)]
+pub struct Locations {
+    // the line and column, both starting at `1`
+    positions: HashMap<String, (usize, usize)>,
+}
+
+enum Frame {
+    // the key of the value being parsed, `None` while expecting a key
+    Mapping(Option<String>),
+    // the index of the next item
+    Sequence(usize),
+}
+
+#[derive(Default)]
+struct Receiver {
+    locations: Locations,
+    frames: Vec<Frame>,
+    // the path of the innermost collection being parsed
+    paths: Vec<String>,
+    // the path of a sequence item, located at its first node instead as long as that's unknown
+    pending_item: Option<String>,
+}
+
+impl Locations {
+    /// Locates everything up to a syntax error, which is reported when loading the file anyway.
+    pub fn parse(source: &str) -> Self {
+        let mut receiver = Receiver::default();
+        let _ = Parser::new(source.chars()).load(&mut receiver, false);
+
+        receiver.locations
+    }
+
+    /// Returns the position of the path or, if it can't be found, of its closest ancestor.
+    pub fn find(&self, path: &str) -> Option<(usize, usize)> {
+        let mut path = path;
+
+        loop {
+            if let Some(position) = self.positions.get(path) {
+                return Some(*position);
+            }
+
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+impl Receiver {
+    fn path(&self) -> &str {
+        self.paths.last().map(String::as_str).unwrap_or("")
+    }
+
+    /// Returns the path of a new node, `None` if the node is a key itself.
+    fn child_path(&mut self, value: Option<&str>, mark: Marker) -> Option<String> {
+        let parent = self.path().to_string();
+        let position = (mark.line(), mark.col() + 1);
+        if let Some(item) = self.pending_item.take() {
+            self.locations.positions.insert(item, position);
+        }
+
+        match self.frames.last_mut() {
+            Some(Frame::Mapping(key @ None)) => {
+                let name = value.unwrap_or("?").to_string();
+                let path = match parent.as_str() {
+                    "" => name.clone(),
+                    _ => format!("{}.{}", parent, name),
+                };
+                self.locations.positions.insert(path, position);
+                *key = Some(name);
+                None
+            }
+            Some(Frame::Mapping(key @ Some(_))) => {
+                let name = key.take().unwrap();
+                Some(match parent.as_str() {
+                    "" => name,
+                    _ => format!("{}.{}", parent, name),
+                })
+            }
+            Some(Frame::Sequence(index)) => {
+                let path = format!("{}[{}]", parent, index);
+                self.locations.positions.insert(path.clone(), position);
+                *index += 1;
+                Some(path)
+            }
+            None => Some(parent),
+        }
+    }
+}
+
+impl MarkedEventReceiver for Receiver {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                self.child_path(Some(&value), mark);
+            }
+            Event::Alias(_) => {
+                self.child_path(None, mark);
+            }
+            Event::MappingStart(_) | Event::SequenceStart(_) => {
+                // complex keys are rather uncommon, so they are located as `?`
+                let path = self
+                    .child_path(None, mark)
+                    .unwrap_or_else(|| format!("{}.?", self.path()));
+                // block mappings start after their first key
+                if let (Event::MappingStart(_), Some(Frame::Sequence(_))) =
+                    (&event, self.frames.last())
+                {
+                    self.pending_item = Some(path.clone());
+                }
+                self.paths.push(path);
+                self.frames.push(match event {
+                    Event::MappingStart(_) => Frame::Mapping(None),
+                    _ => Frame::Sequence(0),
+                });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.pending_item = None;
+                self.paths.pop();
+                self.frames.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Suggests the expected name closest to an unknown one of a deserialization error, e.g.
+/// `ignore-specs` for "unknown field `ignore_specs`, expected one of `ignore-specs`, ...".
+pub fn suggestion(message: &str) -> Option<&str> {
+    if !message.starts_with("unknown ") {
+        return None;
+    }
+
+    let mut names = message.split('`').skip(1).step_by(2);
+    let unknown = names.next()?;
+
+    names
+        .map(|name| (edit_distance(unknown, name), name))
+        .filter(|(distance, _)| *distance <= (unknown.chars().count() / 3).max(2))
+        .min()
+        .map(|(_, name)| name)
+}
+
+/// Returns the names the variants of an enum are deserialized from, by intercepting its
+/// deserialization.
+pub fn variants<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    struct Introspection<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for Introspection<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not an enum"))
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            variants: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = variants;
+            Err(de::Error::custom("introspected"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map struct identifier
+            ignored_any
+        }
+    }
+
+    let mut variants: &'static [&'static str] = &[];
+    let _ = T::deserialize(Introspection(&mut variants));
+
+    variants
+}
+
+/// The number of characters to insert, delete or substitute to turn one text into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = distances[0];
+        distances[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + if ca == *cb { 0 } else { 1 };
+            previous = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(distances[j + 1] + 1);
+        }
+    }
+
+    distances[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::PatternKind;
+    use rstest::rstest;
+
+    const DOCUMENT: &str = "matcher:\n  ignore-specs:\n    - pattern: '**/build'\n    - pattern: target\n      type: dir\n  scopes:\n    - path: Clients\n      skip-specs: [{pattern: out}]\npresets: [rust]\n";
+
+    #[rstest(
+        path,
+        expected,
+        case::key("matcher", Some((1, 1))),
+        case::nested_key("matcher.ignore-specs", Some((2, 3))),
+        case::item("matcher.ignore-specs[1]", Some((4, 7))),
+        case::item_key("matcher.ignore-specs[1].type", Some((5, 7))),
+        case::flow_item_key("matcher.scopes[0].skip-specs[0].pattern", Some((8, 21))),
+        case::flow_item("presets[0]", Some((9, 11))),
+        case::unknown_key("matcher.ignore-specs[1].when-sibling", Some((4, 7))),
+        case::unknown_item("matcher.ignore-specs[4].pattern", Some((2, 3))),
+        case::unknown_root("roots", None)
+    )]
+    fn finds_locations(path: &str, expected: Option<(usize, usize)>) {
+        let locations = Locations::parse(DOCUMENT);
+
+        assert_eq!(expected, locations.find(path));
+    }
+
+    #[test]
+    fn locates_up_to_syntax_errors() {
+        let locations = Locations::parse("matcher:\n  ignore-specs: [\n");
+
+        assert_eq!(Some((2, 3)), locations.find("matcher.ignore-specs"));
+    }
+
+    #[test]
+    fn introspects_variants() {
+        assert_eq!(&["glob", "regex", "path-regex"], variants::<PatternKind>());
+        assert!(variants::<String>().is_empty());
+    }
+
+    #[rstest(
+        message,
+        expected,
+        case::field(
+            "unknown field `ignore_specs`, expected one of `ignore-specs`, `skip-specs`, `rules`",
+            Some("ignore-specs")
+        ),
+        case::single_field("unknown field `paths`, expected `path`", Some("path")),
+        case::variant(
+            "unknown variant `dri`, expected one of `dir`, `file`, `symlink`, `any`",
+            Some("dir")
+        ),
+        case::too_different(
+            "unknown field `foo`, expected one of `ignore-specs`, `skip-specs`",
+            None
+        ),
+        case::other_error("invalid type: string \"foo\", expected `u64`", None)
+    )]
+    fn suggests_names(message: &str, expected: Option<&str>) {
+        assert_eq!(expected, suggestion(message));
+    }
+}
@@ -1,88 +1,205 @@
+#[cfg(windows)]
+use std::ffi::OsString;
+use std::io;
 use std::path::Path;
 #[cfg(windows)]
 use std::ptr;
 
-use log::warn;
-
 #[cfg(windows)]
 use widestring::U16CString;
 #[cfg(windows)]
 use winapi::{
-    shared::minwindef::{DWORD, FALSE, LPCVOID, LPVOID, TRUE},
+    shared::{
+        minwindef::{DWORD, FALSE, LPCVOID, LPVOID, TRUE},
+        winerror::ERROR_FILE_NOT_FOUND,
+    },
     um::{
         errhandlingapi::GetLastError,
-        fileapi::{CreateFile2, ReadFile, WriteFile, CREATE_ALWAYS, OPEN_EXISTING},
-        handleapi::CloseHandle,
+        fileapi::{CreateFile2, DeleteFileW, ReadFile, WriteFile, CREATE_ALWAYS, OPEN_EXISTING},
+        handleapi::{CloseHandle, INVALID_HANDLE_VALUE},
         winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE},
     },
 };
 
+use crate::backend::{IgnoreBackend, IgnoreState};
+use crate::error::Error;
+
 #[cfg(unix)]
 #[cfg(not(target_os = "macos"))]
 const IGNORE_ATTRIBUTE_KEYS: [&str; 1] = ["user.com.dropbox.ignored"];
 #[cfg(target_os = "macos")]
 const IGNORE_ATTRIBUTE_KEYS: [&str; 2] = ["com.dropbox.ignored", "com.apple.fileprovider.ignore#P"];
 #[cfg(windows)]
-const IGNORE_ATTRIBUTE_KEY: &str = "com.dropbox.ignored";
+const IGNORE_ATTRIBUTE_KEYS: [&str; 1] = ["com.dropbox.ignored"];
 const IGNORE_ATTRIBUTE_VALUE_IGNORED: [u8; 1] = [b'1'];
-
-pub struct Dropbox {}
+// marks entries ignored by dropignore (in contrast to ignored manually)
+#[cfg(unix)]
+#[cfg(not(target_os = "macos"))]
+const MANAGED_ATTRIBUTE_KEY: &str = "user.dropignore.managed";
+#[cfg(any(target_os = "macos", windows))]
+const MANAGED_ATTRIBUTE_KEY: &str = "dropignore.managed";
+const MANAGED_ATTRIBUTE_VALUE_MANAGED: [u8; 1] = [b'1'];
+
+pub struct Dropbox {
+    // all of them need to be set for an entry to be ignored
+    ignore_keys: &'static [&'static str],
+}
 
 impl Dropbox {
-    pub fn new() -> Result<Self, String> {
-        Ok(Dropbox {})
+    pub fn new() -> Result<Self, Error> {
+        Ok(Dropbox {
+            ignore_keys: &IGNORE_ATTRIBUTE_KEYS,
+        })
     }
 
-    #[cfg(unix)]
-    pub fn is_ignored(&self, path: &Path) -> bool {
-        for key in IGNORE_ATTRIBUTE_KEYS {
-            if let Ok(Some(bytes)) = xattr::get(path, key) {
-                if bytes.eq(&IGNORE_ATTRIBUTE_VALUE_IGNORED) {
-                    continue;
-                }
-            }
+    /// Uses the given ignore attribute keys instead of the ones of the current platform.
+    #[cfg(test)]
+    pub fn with_ignore_keys(ignore_keys: &'static [&'static str]) -> Self {
+        Dropbox { ignore_keys }
+    }
+
+    fn get(path: &Path, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        xattr_get(path, key).map_err(|e| Error::xattr(path, e))
+    }
+
+    /// Sets the attribute and reads it back, as some file systems silently drop attributes.
+    fn set_verified(path: &Path, key: &str, value: &[u8]) -> Result<(), Error> {
+        xattr_set(path, key, value).map_err(|e| Error::xattr(path, e))?;
 
-            return false;
+        if Self::get(path, key)?.as_deref() != Some(value) {
+            return Err(Error::Unverified {
+                path: path.to_path_buf(),
+                key: key.to_string(),
+            });
         }
 
-        true
+        Ok(())
     }
 
-    #[cfg(unix)]
-    pub fn ignore(&self, path: &Path) -> bool {
-        for key in IGNORE_ATTRIBUTE_KEYS {
-            if let Err(e) = xattr::set(path, key, &IGNORE_ATTRIBUTE_VALUE_IGNORED) {
-                warn!("Failed ignoring {:?} due {:?}", path, e);
-                return false;
+    fn remove(path: &Path, key: &str) -> Result<(), Error> {
+        xattr_remove(path, key).map_err(|e| Error::xattr(path, e))
+    }
+}
+
+impl IgnoreBackend for Dropbox {
+    fn is_ignored(&self, path: &Path) -> Result<bool, Error> {
+        Ok(self.ignore_state(path)? == IgnoreState::Ignored)
+    }
+
+    fn ignore(&self, path: &Path) -> Result<(), Error> {
+        for key in self.ignore_keys {
+            Self::set_verified(path, key, &IGNORE_ATTRIBUTE_VALUE_IGNORED)?;
+        }
+
+        Self::set_verified(
+            path,
+            MANAGED_ATTRIBUTE_KEY,
+            &MANAGED_ATTRIBUTE_VALUE_MANAGED,
+        )
+    }
+
+    fn unignore(&self, path: &Path) -> Result<(), Error> {
+        for key in self.ignore_keys.iter().chain(&[MANAGED_ATTRIBUTE_KEY]) {
+            Self::remove(path, key)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_managed(&self, path: &Path) -> Result<bool, Error> {
+        let value = Self::get(path, MANAGED_ATTRIBUTE_KEY)?;
+
+        Ok(value.as_deref() == Some(&MANAGED_ATTRIBUTE_VALUE_MANAGED))
+    }
+
+    fn manage(&self, path: &Path) -> Result<bool, Error> {
+        if !self.is_ignored(path)? {
+            return Ok(false);
+        }
+
+        Self::set_verified(
+            path,
+            MANAGED_ATTRIBUTE_KEY,
+            &MANAGED_ATTRIBUTE_VALUE_MANAGED,
+        )?;
+
+        Ok(true)
+    }
+
+    fn ignore_state(&self, path: &Path) -> Result<IgnoreState, Error> {
+        let mut ignored_keys = 0;
+        let mut invalid_keys = 0;
+
+        for key in self.ignore_keys {
+            match Self::get(path, key)? {
+                Some(value) if value == IGNORE_ATTRIBUTE_VALUE_IGNORED => ignored_keys += 1,
+                Some(_) => invalid_keys += 1,
+                None => {}
             }
         }
 
-        true
+        if ignored_keys == self.ignore_keys.len() {
+            return Ok(IgnoreState::Ignored);
+        }
+        if ignored_keys > 0 {
+            return Ok(IgnoreState::Partial);
+        }
+
+        // a managed marker without any ignore attribute is a leftover as well
+        if invalid_keys > 0 || Self::get(path, MANAGED_ATTRIBUTE_KEY)?.is_some() {
+            return Ok(IgnoreState::Invalid);
+        }
+
+        Ok(IgnoreState::NotIgnored)
     }
 
-    #[cfg(windows)]
-    pub fn is_ignored(&self, path: &Path) -> bool {
-        let attribute = xattr_get(path, IGNORE_ATTRIBUTE_KEY);
-        if let Some(bytes) = attribute {
-            if bytes.eq(&IGNORE_ATTRIBUTE_VALUE_IGNORED) {
-                return true;
+    fn repair(&self, path: &Path) -> Result<(), Error> {
+        match self.ignore_state(path)? {
+            IgnoreState::Partial => {
+                for key in self.ignore_keys {
+                    Self::set_verified(path, key, &IGNORE_ATTRIBUTE_VALUE_IGNORED)?;
+                }
+                Ok(())
             }
-            return false;
+            IgnoreState::Invalid => self.unignore(path),
+            IgnoreState::Ignored | IgnoreState::NotIgnored => Ok(()),
         }
-        false
     }
+}
 
-    #[cfg(windows)]
-    pub fn ignore(&self, path: &Path) -> bool {
-        xattr_set(path, IGNORE_ATTRIBUTE_KEY, &IGNORE_ATTRIBUTE_VALUE_IGNORED)
+#[cfg(unix)]
+fn xattr_get(path: &Path, attribute_name: &str) -> io::Result<Option<Vec<u8>>> {
+    xattr::get(path, attribute_name)
+}
+
+#[cfg(unix)]
+fn xattr_set(path: &Path, attribute_name: &str, attribute_value: &[u8]) -> io::Result<()> {
+    xattr::set(path, attribute_name, attribute_value)
+}
+
+#[cfg(unix)]
+fn xattr_remove(path: &Path, attribute_name: &str) -> io::Result<()> {
+    // removing an absent attribute is an error, so only touch present ones
+    if xattr::get(path, attribute_name)?.is_none() {
+        return Ok(());
     }
+
+    xattr::remove(path, attribute_name)
+}
+
+#[cfg(windows)]
+fn stream_path(path: &Path, attribute_name: &str) -> U16CString {
+    let mut stream_path = OsString::from(path.as_os_str());
+    stream_path.push(":");
+    stream_path.push(attribute_name);
+
+    unsafe { U16CString::from_os_str_unchecked(&stream_path) }
 }
 
 #[cfg(windows)]
-fn xattr_get(path: &Path, attribute_name: &str) -> Option<Vec<u8>> {
+fn xattr_get(path: &Path, attribute_name: &str) -> io::Result<Option<Vec<u8>>> {
     unsafe {
-        let stream_path = format!("{}:{}", path.to_str().unwrap(), attribute_name);
-        let winapi_path = U16CString::from_str_unchecked(&stream_path);
+        let winapi_path = stream_path(path, attribute_name);
 
         let handle = CreateFile2(
             winapi_path.as_ptr(),
@@ -92,14 +209,18 @@ fn xattr_get(path: &Path, attribute_name: &str) -> Option<Vec<u8>> {
             ptr::null_mut(),
         );
 
-        let error = GetLastError();
-        if error != 0 {
-            return None;
+        if handle == INVALID_HANDLE_VALUE {
+            let last_error = GetLastError();
+            // the stream not being present means the attribute isn't set
+            if last_error == ERROR_FILE_NOT_FOUND {
+                return Ok(None);
+            }
+            return Err(io::Error::from_raw_os_error(last_error as i32));
         }
 
         let mut data = [0u8; 2];
         let mut len = 0;
-        let error = ReadFile(
+        let success = ReadFile(
             handle,
             data.as_mut_ptr() as LPVOID,
             data.len() as u32,
@@ -107,29 +228,24 @@ fn xattr_get(path: &Path, attribute_name: &str) -> Option<Vec<u8>> {
             ptr::null_mut(),
         );
 
-        if error != TRUE {
+        if success != TRUE {
             let last_error = GetLastError();
-            warn!(
-                "Failed reading stream data from {:?} with {}",
-                stream_path, last_error
-            );
             CloseHandle(handle);
-            return None;
+            return Err(io::Error::from_raw_os_error(last_error as i32));
         }
 
         CloseHandle(handle);
         let mut attribute_data = data.to_vec();
         attribute_data.truncate(len as usize);
 
-        Some(attribute_data)
+        Ok(Some(attribute_data))
     }
 }
 
 #[cfg(windows)]
-fn xattr_set(path: &Path, attribute_name: &str, attribute_value: &[u8]) -> bool {
+fn xattr_set(path: &Path, attribute_name: &str, attribute_value: &[u8]) -> io::Result<()> {
     unsafe {
-        let stream_path = format!("{}:{}", path.to_str().unwrap(), attribute_name);
-        let winapi_path = U16CString::from_str_unchecked(&stream_path);
+        let winapi_path = stream_path(path, attribute_name);
 
         let handle = CreateFile2(
             winapi_path.as_ptr(),
@@ -139,13 +255,8 @@ fn xattr_set(path: &Path, attribute_name: &str, attribute_value: &[u8]) -> bool
             ptr::null_mut(),
         );
 
-        let last_error = GetLastError();
-        if last_error != 0 {
-            warn!(
-                "Failed writing stream data to {:?} with {}",
-                stream_path, last_error
-            );
-            return false;
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::from_raw_os_error(GetLastError() as i32));
         }
 
         let mut bytes_written = 0;
@@ -159,22 +270,36 @@ fn xattr_set(path: &Path, attribute_name: &str, attribute_value: &[u8]) -> bool
 
         if success == FALSE {
             let last_error = GetLastError();
-            warn!(
-                "Failed writing stream data to {:?} with {}",
-                stream_path, last_error
-            );
             CloseHandle(handle);
-            return false;
+            return Err(io::Error::from_raw_os_error(last_error as i32));
         }
 
         CloseHandle(handle);
-        true
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+fn xattr_remove(path: &Path, attribute_name: &str) -> io::Result<()> {
+    unsafe {
+        let winapi_path = stream_path(path, attribute_name);
+
+        if DeleteFileW(winapi_path.as_ptr()) == FALSE {
+            let last_error = GetLastError();
+            // the stream not being present is fine, the attribute isn't set then
+            if last_error != ERROR_FILE_NOT_FOUND {
+                return Err(io::Error::from_raw_os_error(last_error as i32));
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Dropbox;
+    use crate::backend::{IgnoreBackend, IgnoreState};
     use directories::BaseDirs;
     use rstest::rstest;
     use std::fs;
@@ -189,8 +314,13 @@ mod tests {
     const TEST_IGNORE_ATTRIBUTE_KEYS: [&str; 2] =
         ["com.dropbox.ignored", "com.apple.fileprovider.ignore#P"];
     #[cfg(windows)]
-    const TEST_IGNORE_ATTRIBUTE_KEY: &str = "com.dropbox.ignored";
+    const TEST_IGNORE_ATTRIBUTE_KEYS: [&str; 1] = ["com.dropbox.ignored"];
     const TEST_IGNORE_ATTRIBUTE_VALUE_IGNORED: [u8; 1] = [b'1'];
+    // mimics the macOS key set on every platform
+    const TEST_MULTIPLE_IGNORE_ATTRIBUTE_KEYS: [&str; 2] = [
+        "user.dropignore-testing.ignored",
+        "user.dropignore-testing.fileprovider.ignore#P",
+    ];
 
     #[rstest(arrange_ignored, case::not_ignored(false), case::ignored(true))]
     fn is_ignored_directory(arrange_ignored: bool) {
@@ -203,7 +333,7 @@ mod tests {
 
         let dropbox = Dropbox::new().unwrap();
 
-        assert_eq!(arrange_ignored, dropbox.is_ignored(path));
+        assert_eq!(arrange_ignored, dropbox.is_ignored(path).unwrap());
     }
 
     #[rstest(arrange_ignored, case::not_ignored(false), case::ignored(true))]
@@ -217,7 +347,7 @@ mod tests {
 
         let dropbox = Dropbox::new().unwrap();
 
-        assert_eq!(arrange_ignored, dropbox.is_ignored(path));
+        assert_eq!(arrange_ignored, dropbox.is_ignored(path).unwrap());
     }
 
     #[test]
@@ -227,9 +357,9 @@ mod tests {
 
         let dropbox = Dropbox::new().unwrap();
 
-        dropbox.ignore(path);
+        dropbox.ignore(path).unwrap();
 
-        assert!(dropbox.is_ignored(path));
+        assert!(dropbox.is_ignored(path).unwrap());
     }
 
     #[test]
@@ -239,9 +369,158 @@ mod tests {
 
         let dropbox = Dropbox::new().unwrap();
 
-        dropbox.ignore(path);
+        dropbox.ignore(path).unwrap();
 
-        assert!(dropbox.is_ignored(path));
+        assert!(dropbox.is_ignored(path).unwrap());
+    }
+
+    #[rstest(arrange_ignored, case::not_ignored(false), case::ignored(true))]
+    fn unignore_directory(arrange_ignored: bool) {
+        let temp = arrange_test_directory();
+        let path = temp.path();
+
+        if arrange_ignored {
+            arrange_ignored_attribute(path);
+        }
+
+        let dropbox = Dropbox::new().unwrap();
+
+        dropbox.unignore(path).unwrap();
+        assert!(!dropbox.is_ignored(path).unwrap());
+    }
+
+    #[rstest(arrange_ignored, case::not_ignored(false), case::ignored(true))]
+    fn unignore_file(arrange_ignored: bool) {
+        let temp = arrange_test_file();
+        let path = temp.path();
+
+        if arrange_ignored {
+            arrange_ignored_attribute(path);
+        }
+
+        let dropbox = Dropbox::new().unwrap();
+
+        dropbox.unignore(path).unwrap();
+        assert!(!dropbox.is_ignored(path).unwrap());
+    }
+
+    #[test]
+    fn ignore_marks_managed() {
+        let temp = arrange_test_directory();
+        let path = temp.path();
+
+        let dropbox = Dropbox::new().unwrap();
+
+        assert!(!dropbox.is_managed(path).unwrap());
+
+        dropbox.ignore(path).unwrap();
+
+        assert!(dropbox.is_managed(path).unwrap());
+
+        dropbox.unignore(path).unwrap();
+
+        assert!(!dropbox.is_managed(path).unwrap());
+    }
+
+    #[test]
+    fn manage_foreign_ignore() {
+        let temp = arrange_test_directory();
+        let path = temp.path();
+
+        arrange_ignored_attribute(path);
+
+        let dropbox = Dropbox::new().unwrap();
+
+        assert!(!dropbox.is_managed(path).unwrap());
+
+        assert!(dropbox.manage(path).unwrap());
+
+        assert!(dropbox.is_ignored(path).unwrap());
+        assert!(dropbox.is_managed(path).unwrap());
+    }
+
+    #[test]
+    fn ignore_with_multiple_keys() {
+        let temp = arrange_test_directory();
+        let path = temp.path();
+
+        let dropbox = Dropbox::with_ignore_keys(&TEST_MULTIPLE_IGNORE_ATTRIBUTE_KEYS);
+
+        dropbox.ignore(path).unwrap();
+
+        assert!(dropbox.is_ignored(path).unwrap());
+        for key in TEST_MULTIPLE_IGNORE_ATTRIBUTE_KEYS {
+            assert_eq!(
+                Some(TEST_IGNORE_ATTRIBUTE_VALUE_IGNORED.to_vec()),
+                super::xattr_get(path, key).unwrap()
+            );
+        }
+    }
+
+    #[rstest(
+        first_value,
+        second_value,
+        managed,
+        expected,
+        case::not_ignored(None, None, false, IgnoreState::NotIgnored),
+        case::ignored(Some("1"), Some("1"), false, IgnoreState::Ignored),
+        case::partial_first(Some("1"), None, false, IgnoreState::Partial),
+        case::partial_second(None, Some("1"), true, IgnoreState::Partial),
+        case::partial_with_invalid(Some("1"), Some("true"), false, IgnoreState::Partial),
+        case::invalid(Some("0"), None, false, IgnoreState::Invalid),
+        case::leftover_managed(None, None, true, IgnoreState::Invalid)
+    )]
+    fn ignore_state_with_multiple_keys(
+        first_value: Option<&str>,
+        second_value: Option<&str>,
+        managed: bool,
+        expected: IgnoreState,
+    ) {
+        let temp = arrange_test_directory();
+        let path = temp.path();
+
+        arrange_attributes(path, first_value, second_value, managed);
+
+        let dropbox = Dropbox::with_ignore_keys(&TEST_MULTIPLE_IGNORE_ATTRIBUTE_KEYS);
+
+        assert_eq!(expected, dropbox.ignore_state(path).unwrap());
+        assert_eq!(
+            expected == IgnoreState::Ignored,
+            dropbox.is_ignored(path).unwrap()
+        );
+    }
+
+    #[rstest(
+        first_value,
+        second_value,
+        managed,
+        expected,
+        case::partial(Some("1"), None, true, IgnoreState::Ignored),
+        case::partial_with_invalid(None, Some("1"), false, IgnoreState::Ignored),
+        case::invalid(Some("0"), Some("yes"), false, IgnoreState::NotIgnored),
+        case::leftover_managed(None, None, true, IgnoreState::NotIgnored)
+    )]
+    fn repair_with_multiple_keys(
+        first_value: Option<&str>,
+        second_value: Option<&str>,
+        managed: bool,
+        expected: IgnoreState,
+    ) {
+        let temp = arrange_test_directory();
+        let path = temp.path();
+
+        arrange_attributes(path, first_value, second_value, managed);
+
+        let dropbox = Dropbox::with_ignore_keys(&TEST_MULTIPLE_IGNORE_ATTRIBUTE_KEYS);
+
+        dropbox.repair(path).unwrap();
+
+        assert_eq!(expected, dropbox.ignore_state(path).unwrap());
+        // the managed marker is kept for repaired ignores
+        assert_eq!(
+            managed && expected == IgnoreState::Ignored,
+            dropbox.is_managed(path).unwrap()
+        );
     }
 
     fn arrange_test_directory() -> TempDir {
@@ -284,19 +563,27 @@ mod tests {
         cache_dir.into()
     }
 
-    #[cfg(unix)]
     fn arrange_ignored_attribute(path: &Path) {
         for key in TEST_IGNORE_ATTRIBUTE_KEYS {
-            xattr::set(path, key, &TEST_IGNORE_ATTRIBUTE_VALUE_IGNORED).unwrap();
+            super::xattr_set(path, key, &TEST_IGNORE_ATTRIBUTE_VALUE_IGNORED).unwrap();
         }
     }
 
-    #[cfg(windows)]
-    fn arrange_ignored_attribute(path: &Path) {
-        super::xattr_set(
-            path,
-            TEST_IGNORE_ATTRIBUTE_KEY,
-            &TEST_IGNORE_ATTRIBUTE_VALUE_IGNORED,
-        );
+    fn arrange_attributes(
+        path: &Path,
+        first_value: Option<&str>,
+        second_value: Option<&str>,
+        managed: bool,
+    ) {
+        let values = [first_value, second_value];
+        for (key, value) in TEST_MULTIPLE_IGNORE_ATTRIBUTE_KEYS.iter().zip(&values) {
+            if let Some(value) = value {
+                super::xattr_set(path, key, value.as_bytes()).unwrap();
+            }
+        }
+
+        if managed {
+            super::xattr_set(path, super::MANAGED_ATTRIBUTE_KEY, b"1").unwrap();
+        }
     }
 }
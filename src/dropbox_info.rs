@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use directories::BaseDirs;
+use log::debug;
+use serde::Deserialize;
+
+use crate::error::Error;
+
+/// The Dropbox accounts as listed in the `info.json` file written by the Dropbox client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Account {
+    Personal,
+    Business,
+    All,
+}
+
+impl Account {
+    pub const VARIANTS: [&'static str; 3] = ["personal", "business", "all"];
+}
+
+impl FromStr for Account {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "personal" => Ok(Account::Personal),
+            "business" => Ok(Account::Business),
+            "all" => Ok(Account::All),
+            _ => Err(format!("Unknown account {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Info {
+    personal: Option<AccountInfo>,
+    business: Option<AccountInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountInfo {
+    path: PathBuf,
+}
+
+/// Returns the location of the `info.json` file in case it exists.
+pub fn info_file_path() -> Option<PathBuf> {
+    let base_dirs = BaseDirs::new()?;
+
+    let candidates = if cfg!(windows) {
+        vec![
+            base_dirs.data_dir().join("Dropbox").join("info.json"),
+            base_dirs.data_local_dir().join("Dropbox").join("info.json"),
+        ]
+    } else {
+        vec![base_dirs.home_dir().join(".dropbox").join("info.json")]
+    };
+
+    candidates.into_iter().find(|p| {
+        debug!("Evaluating {:?}", p);
+        p.is_file()
+    })
+}
+
+/// Reads the root folders of the given account(s) from the given `info.json` file.
+pub fn roots(info_file_path: &Path, account: Account) -> Result<Vec<PathBuf>, Error> {
+    let content = fs::read_to_string(info_file_path).map_err(|e| Error::io(info_file_path, e))?;
+    let info: Info = serde_json::from_str(&content).map_err(|e| Error::DropboxInfo {
+        path: info_file_path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+
+    let account_infos = match account {
+        Account::Personal => vec![info.personal],
+        Account::Business => vec![info.business],
+        Account::All => vec![info.personal, info.business],
+    };
+
+    let roots: Vec<PathBuf> = account_infos
+        .into_iter()
+        .flatten()
+        .map(|a| a.path)
+        .collect();
+
+    if roots.is_empty() {
+        return Err(Error::DropboxInfo {
+            path: info_file_path.to_path_buf(),
+            reason: format!("No {:?} account found", account),
+        });
+    }
+
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    #[rstest(
+        account,
+        expected,
+        case::personal(Account::Personal, vec!["/home/charly/Dropbox"]),
+        case::business(Account::Business, vec!["/home/charly/Dropbox (Acme)"]),
+        case::all(Account::All, vec!["/home/charly/Dropbox", "/home/charly/Dropbox (Acme)"])
+    )]
+    fn roots_from_info_file(account: Account, expected: Vec<&str>) {
+        let roots = roots(&fixture_path(), account).unwrap();
+
+        let expected: Vec<PathBuf> = expected.into_iter().map(PathBuf::from).collect();
+        assert_eq!(expected, roots);
+    }
+
+    #[test]
+    fn roots_of_missing_account() {
+        let temp = NamedTempFile::new().unwrap();
+        fs::write(
+            temp.path(),
+            r#"{"personal": {"path": "/home/charly/Dropbox"}}"#,
+        )
+        .unwrap();
+
+        let roots = roots(temp.path(), Account::Business);
+
+        assert!(roots.is_err());
+        assert!(roots
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("No Business account found"));
+    }
+
+    #[test]
+    fn roots_from_broken_info_file() {
+        let temp = NamedTempFile::new().unwrap();
+        fs::write(temp.path(), "{").unwrap();
+
+        let roots = roots(temp.path(), Account::All);
+
+        assert!(roots.is_err());
+        assert!(matches!(roots.err().unwrap(), Error::DropboxInfo { .. }));
+    }
+
+    fn fixture_path() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/info.json")
+    }
+}
@@ -0,0 +1,248 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use config::ConfigError;
+
+#[derive(Debug)]
+pub enum Error {
+    Config(ConfigError),
+    /// an invalid value of the configuration file, located by its key
+    ConfigFile {
+        file: PathBuf,
+        key: String,
+        // the line and column, `None` if the key can't be found in the file (e.g. from a preset)
+        position: Option<(usize, usize)>,
+        reason: String,
+    },
+    Pattern {
+        specs: String,
+        index: usize,
+        source: globset::Error,
+    },
+    Regex {
+        specs: String,
+        index: usize,
+        source: fancy_regex::Error,
+    },
+    Spec {
+        specs: String,
+        index: usize,
+        reason: String,
+    },
+    Xattr {
+        path: PathBuf,
+        kind: XattrErrorKind,
+        source: io::Error,
+    },
+    IgnoreFile {
+        path: PathBuf,
+        source: ignore::Error,
+    },
+    Unverified {
+        path: PathBuf,
+        key: String,
+    },
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Walk(walkdir::Error),
+    Watch(notify::Error),
+    DropboxInfo {
+        path: PathBuf,
+        reason: String,
+    },
+    Unrepresentable {
+        path: PathBuf,
+        file: PathBuf,
+    },
+}
+
+/// The reason of a failed extended file system attribute operation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum XattrErrorKind {
+    /// the file system doesn't support extended attributes (ENOTSUP)
+    NotSupported,
+    /// missing permissions to access the entry (EACCES, EPERM)
+    PermissionDenied,
+    /// the file system is mounted read-only (EROFS)
+    ReadOnly,
+    /// the entry doesn't exist (anymore) (ENOENT)
+    NotFound,
+    Other,
+}
+
+impl XattrErrorKind {
+    pub fn from_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => return XattrErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound => return XattrErrorKind::NotFound,
+            _ => {}
+        }
+
+        match error.raw_os_error() {
+            #[cfg(unix)]
+            Some(libc::EROFS) => XattrErrorKind::ReadOnly,
+            #[cfg(unix)]
+            Some(code) if code == libc::ENOTSUP || code == libc::EOPNOTSUPP => {
+                XattrErrorKind::NotSupported
+            }
+            // ERROR_WRITE_PROTECT
+            #[cfg(windows)]
+            Some(19) => XattrErrorKind::ReadOnly,
+            // ERROR_NOT_SUPPORTED
+            #[cfg(windows)]
+            Some(50) => XattrErrorKind::NotSupported,
+            _ => XattrErrorKind::Other,
+        }
+    }
+}
+
+impl Error {
+    pub fn xattr(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Xattr {
+            path: path.into(),
+            kind: XattrErrorKind::from_io(&source),
+            source,
+        }
+    }
+
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config(e) => write!(f, "Invalid configuration: {}", e),
+            Error::ConfigFile {
+                file,
+                key,
+                position: Some((line, column)),
+                reason,
+            } => write!(
+                f,
+                "Invalid configuration {}:{}:{} at `{}`: {}",
+                file.display(),
+                line,
+                column,
+                key,
+                reason
+            ),
+            Error::ConfigFile {
+                file,
+                key,
+                position: None,
+                reason,
+            } => write!(
+                f,
+                "Invalid configuration {} at `{}`: {}",
+                file.display(),
+                key,
+                reason
+            ),
+            Error::Pattern {
+                specs,
+                index,
+                source,
+            } => write!(f, "Invalid pattern in {}[{}]: {}", specs, index, source),
+            Error::Regex {
+                specs,
+                index,
+                source,
+            } => write!(
+                f,
+                "Invalid regular expression in {}[{}]: {}",
+                specs, index, source
+            ),
+            Error::Spec {
+                specs,
+                index,
+                reason,
+            } => write!(f, "Invalid spec {}[{}]: {}", specs, index, reason),
+            Error::Xattr { path, kind, source } => write!(
+                f,
+                "Failed accessing attributes of {:?} ({:?}): {}",
+                path, kind, source
+            ),
+            Error::IgnoreFile { path, source } => {
+                write!(f, "Invalid ignore file {:?}: {}", path, source)
+            }
+            Error::Unverified { path, key } => write!(
+                f,
+                "Attribute {} of {:?} didn't persist (read back a different value)",
+                key, path
+            ),
+            Error::Io { path, source } => write!(f, "Failed accessing {:?}: {}", path, source),
+            Error::Walk(e) => write!(f, "Failed walking directory: {}", e),
+            Error::Watch(e) => write!(f, "Failed watching directory: {}", e),
+            Error::DropboxInfo { path, reason } => {
+                write!(f, "Failed reading Dropbox info {:?}: {}", path, reason)
+            }
+            Error::Unrepresentable { path, file } => {
+                write!(f, "Path {:?} can't be represented in {:?}", path, file)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(e) => Some(e),
+            Error::Pattern { source, .. } => Some(source),
+            Error::Regex { source, .. } => Some(source),
+            Error::IgnoreFile { source, .. } => Some(source),
+            Error::Xattr { source, .. } | Error::Io { source, .. } => Some(source),
+            Error::Walk(e) => Some(e),
+            Error::Watch(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Error::Config(e)
+    }
+}
+
+impl From<walkdir::Error> for Error {
+    fn from(e: walkdir::Error) -> Self {
+        Error::Walk(e)
+    }
+}
+
+impl From<notify::Error> for Error {
+    fn from(e: notify::Error) -> Self {
+        Error::Watch(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[cfg(unix)]
+    #[rstest(
+        code,
+        expected,
+        case::not_supported(libc::ENOTSUP, XattrErrorKind::NotSupported),
+        case::access(libc::EACCES, XattrErrorKind::PermissionDenied),
+        case::permission(libc::EPERM, XattrErrorKind::PermissionDenied),
+        case::read_only(libc::EROFS, XattrErrorKind::ReadOnly),
+        case::not_found(libc::ENOENT, XattrErrorKind::NotFound),
+        case::other(libc::EIO, XattrErrorKind::Other)
+    )]
+    fn xattr_error_kind(code: i32, expected: XattrErrorKind) {
+        let error = io::Error::from_raw_os_error(code);
+
+        assert_eq!(expected, XattrErrorKind::from_io(&error));
+    }
+}
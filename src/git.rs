@@ -0,0 +1,193 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::Gitignore;
+use log::warn;
+
+use crate::error::Error;
+use crate::ignore_file::{self, FileMatch, IgnoreFiles, GITIGNORE_FILE_NAME};
+
+const GIT_DIR_NAME: &str = ".git";
+pub const EXCLUDE_FILE_PATH: &str = ".git/info/exclude";
+// the global excludes file is determined by git's configuration
+const GLOBAL_EXCLUDES_FILE_NAME: &str = "core.excludesFile";
+
+/// Consults the ignore rules of git for entries inside git work trees, i.e. the `.gitignore`
+/// files, `.git/info/exclude` and the global `core.excludesFile` (in this order of precedence).
+///
+/// Only work trees below one of the enabled directories are taken into account.
+pub struct GitExcludes {
+    directories: Vec<PathBuf>,
+    gitignore_files: IgnoreFiles,
+    // the work tree containing a directory, `None` if it's not inside any
+    work_trees: RefCell<HashMap<PathBuf, Option<PathBuf>>>,
+    exclude_files: RefCell<HashMap<PathBuf, Option<Gitignore>>>,
+    global_excludes: Gitignore,
+}
+
+impl GitExcludes {
+    pub fn new(directories: &[PathBuf]) -> Self {
+        let (global_excludes, error) = Gitignore::global();
+        if let Some(e) = error {
+            warn!("Failed reading global git excludes: {}", e);
+        }
+
+        Self::with_global_excludes(directories, global_excludes)
+    }
+
+    pub fn with_global_excludes(directories: &[PathBuf], global_excludes: Gitignore) -> Self {
+        GitExcludes {
+            directories: directories
+                .iter()
+                .map(|d| d.canonicalize().unwrap_or_else(|_| d.clone()))
+                .collect(),
+            gitignore_files: IgnoreFiles::new(GITIGNORE_FILE_NAME),
+            work_trees: Default::default(),
+            exclude_files: Default::default(),
+            global_excludes,
+        }
+    }
+
+    pub fn evaluate(&self, path: &Path) -> Result<Option<FileMatch>, Error> {
+        if !self.directories.iter().any(|d| path.starts_with(d)) {
+            return Ok(None);
+        }
+
+        let work_tree = match path.parent().and_then(|p| self.work_tree(p)) {
+            Some(work_tree) => work_tree,
+            None => return Ok(None),
+        };
+
+        if let Some(file_match) = self.gitignore_files.evaluate(path, &work_tree)? {
+            return Ok(Some(file_match));
+        }
+
+        let is_dir = path.is_dir();
+
+        if let Some(file_match) = self.evaluate_exclude_file(&work_tree, path, is_dir)? {
+            return Ok(Some(file_match));
+        }
+
+        // the global excludes apply to every work tree, so match relative to the current one
+        let relative_path = path.strip_prefix(&work_tree).unwrap_or(path);
+        Ok(ignore_file::matched(
+            &self.global_excludes,
+            relative_path,
+            is_dir,
+            || PathBuf::from(GLOBAL_EXCLUDES_FILE_NAME),
+        ))
+    }
+
+    /// Drops any cached rules of the given directory, so they get reloaded when needed again.
+    pub fn invalidate(&self, directory: &Path) {
+        self.gitignore_files.invalidate(directory);
+        self.exclude_files.borrow_mut().remove(directory);
+    }
+
+    pub fn is_rules_file(path: &Path) -> bool {
+        path.file_name()
+            .map(|n| n == GITIGNORE_FILE_NAME)
+            .unwrap_or(false)
+            || path.ends_with(EXCLUDE_FILE_PATH)
+    }
+
+    fn work_tree(&self, directory: &Path) -> Option<PathBuf> {
+        if let Some(work_tree) = self.work_trees.borrow().get(directory) {
+            return work_tree.clone();
+        }
+
+        // a `.git` file denotes a linked work tree or a submodule
+        let work_tree = if directory.join(GIT_DIR_NAME).exists() {
+            Some(directory.to_path_buf())
+        } else {
+            directory.parent().and_then(|p| self.work_tree(p))
+        };
+
+        self.work_trees
+            .borrow_mut()
+            .insert(directory.to_path_buf(), work_tree.clone());
+
+        work_tree
+    }
+
+    fn evaluate_exclude_file(
+        &self,
+        work_tree: &Path,
+        path: &Path,
+        is_dir: bool,
+    ) -> Result<Option<FileMatch>, Error> {
+        let exclude_file = work_tree.join(EXCLUDE_FILE_PATH);
+
+        if !self.exclude_files.borrow().contains_key(work_tree) {
+            let gitignore = ignore_file::load(&exclude_file, work_tree)?;
+            self.exclude_files
+                .borrow_mut()
+                .insert(work_tree.to_path_buf(), gitignore);
+        }
+
+        let exclude_files = self.exclude_files.borrow();
+        let gitignore = match exclude_files.get(work_tree) {
+            Some(Some(gitignore)) => gitignore,
+            _ => return Ok(None),
+        };
+
+        Ok(ignore_file::matched(gitignore, path, is_dir, || {
+            exclude_file
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ignore::gitignore::GitignoreBuilder;
+    use rstest::rstest;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[rstest(
+        path,
+        expected_file,
+        case::gitignore("repo/target", Some("repo/.gitignore")),
+        case::nested_gitignore("repo/sub/generated", Some("repo/sub/.gitignore")),
+        case::exclude("repo/.idea", Some("repo/.git/info/exclude")),
+        case::global("repo/debug.log", Some("core.excludesFile")),
+        case::not_ignored("repo/src", None),
+        case::outside_of_work_tree("plain/target", None),
+        case::outside_of_directories("other/target", None)
+    )]
+    fn evaluates_git_rules(path: &str, expected_file: Option<&str>) {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        arrange_file(&root.join("project/repo/.gitignore"), "target\n");
+        arrange_file(&root.join("project/repo/sub/.gitignore"), "generated\n");
+        arrange_file(&root.join("project/repo/.git/info/exclude"), ".idea\n");
+        arrange_file(&root.join("project/plain/.gitignore"), "target\n");
+        arrange_file(&root.join("other/.git/info/exclude"), "");
+        arrange_file(&root.join("other/.gitignore"), "target\n");
+
+        let mut builder = GitignoreBuilder::new("");
+        builder.add_line(None, "*.log").unwrap();
+        let git_excludes =
+            GitExcludes::with_global_excludes(&[root.join("project")], builder.build().unwrap());
+
+        let path = if path.starts_with("other") {
+            root.join(path)
+        } else {
+            root.join("project").join(path)
+        };
+        let file_match = git_excludes.evaluate(&path).unwrap();
+
+        let expected_file = expected_file.map(|f| match f {
+            GLOBAL_EXCLUDES_FILE_NAME => PathBuf::from(f),
+            f => root.join("project").join(f),
+        });
+        assert_eq!(expected_file, file_match.map(|m| m.file));
+    }
+
+    fn arrange_file(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+use crate::error::Error;
+
+pub const DROPIGNORE_FILE_NAME: &str = ".dropignore";
+pub const GITIGNORE_FILE_NAME: &str = ".gitignore";
+
+/// The decision of the closest ignore file having a say about an entry.
+#[derive(Debug, PartialEq)]
+pub struct FileMatch {
+    /// `false` if the entry got re-included by a `!` pattern
+    pub ignore: bool,
+    pub file: PathBuf,
+    pub pattern: String,
+}
+
+/// Discovers ignore files (e.g. `.dropignore`) in the directories of the evaluated entries.
+///
+/// The patterns follow the gitignore semantics relative to the directory the file lives in.
+/// Files in deeper directories take precedence over the ones further up.
+pub struct IgnoreFiles {
+    file_name: &'static str,
+    // parsed files by their directory, `None` if the directory doesn't contain one
+    cache: RefCell<HashMap<PathBuf, Option<Gitignore>>>,
+}
+
+impl IgnoreFiles {
+    pub fn new(file_name: &'static str) -> Self {
+        IgnoreFiles {
+            file_name,
+            cache: Default::default(),
+        }
+    }
+
+    /// Evaluates the path against the ignore files of all its ancestors up to `top`, e.g. the
+    /// scanned directory.
+    pub fn evaluate(&self, path: &Path, top: &Path) -> Result<Option<FileMatch>, Error> {
+        let is_dir = path.is_dir();
+
+        let directories = path.ancestors().skip(1).take_while(|d| d.starts_with(top));
+
+        for directory in directories {
+            if !self.cache.borrow().contains_key(directory) {
+                let gitignore = load(&directory.join(self.file_name), directory)?;
+                self.cache
+                    .borrow_mut()
+                    .insert(directory.to_path_buf(), gitignore);
+            }
+
+            let cache = self.cache.borrow();
+            let gitignore = match cache.get(directory) {
+                Some(Some(gitignore)) => gitignore,
+                _ => continue,
+            };
+
+            if let Some(file_match) =
+                matched(gitignore, path, is_dir, || directory.join(self.file_name))
+            {
+                return Ok(Some(file_match));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Drops the parsed file of the given directory, so it gets reloaded when needed again.
+    pub fn invalidate(&self, directory: &Path) {
+        self.cache.borrow_mut().remove(directory);
+    }
+}
+
+/// Loads the ignore file with patterns relative to `root`. Returns `None` if there's no file.
+pub fn load(file: &Path, root: &Path) -> Result<Option<Gitignore>, Error> {
+    if !file.is_file() {
+        return Ok(None);
+    }
+
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(source) = builder.add(file) {
+        return Err(Error::IgnoreFile {
+            path: file.to_path_buf(),
+            source,
+        });
+    }
+
+    let gitignore = builder.build().map_err(|source| Error::IgnoreFile {
+        path: file.to_path_buf(),
+        source,
+    })?;
+
+    Ok(Some(gitignore))
+}
+
+/// Matches the path against the parsed ignore file, naming the file lazily.
+pub fn matched(
+    gitignore: &Gitignore,
+    path: &Path,
+    is_dir: bool,
+    file: impl FnOnce() -> PathBuf,
+) -> Option<FileMatch> {
+    let (ignore, glob) = match gitignore.matched(path, is_dir) {
+        Match::Ignore(glob) => (true, glob),
+        Match::Whitelist(glob) => (false, glob),
+        Match::None => return None,
+    };
+
+    Some(FileMatch {
+        ignore,
+        file: file(),
+        pattern: glob.original().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[rstest(
+        path,
+        expected_ignore,
+        expected_file,
+        case::ignored("a/build", Some(true), ""),
+        case::relative_to_file("build", None, ""),
+        case::anchored("a/out", Some(true), ""),
+        case::anchored_deeper("a/b/out", None, ""),
+        case::re_included("a/b/build", Some(false), "b"),
+        case::overridden_deeper("a/b/dist", Some(true), "b"),
+        case::not_matching("a/src", None, "")
+    )]
+    fn evaluates_with_precedence(path: &str, expected_ignore: Option<bool>, expected_file: &str) {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("root");
+        arrange_file(&root.join("a").join(DROPIGNORE_FILE_NAME), "build\n/out\n");
+        arrange_file(
+            &root.join("a/b").join(DROPIGNORE_FILE_NAME),
+            "!build\ndist/\n",
+        );
+        for dir in &[
+            "a/build",
+            "a/out",
+            "a/b/out",
+            "a/b/build",
+            "a/b/dist",
+            "a/src",
+        ] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let files = IgnoreFiles::new(DROPIGNORE_FILE_NAME);
+        let file_match = files.evaluate(&root.join(path), &root).unwrap();
+
+        assert_eq!(expected_ignore, file_match.as_ref().map(|m| m.ignore));
+        if let Some(file_match) = file_match {
+            let expected_file = root
+                .join("a")
+                .join(expected_file)
+                .join(DROPIGNORE_FILE_NAME);
+            assert_eq!(expected_file, file_match.file);
+        }
+    }
+
+    #[test]
+    fn stops_at_top() {
+        let temp = TempDir::new().unwrap();
+        let top = temp.path().join("top");
+        arrange_file(&temp.path().join(GITIGNORE_FILE_NAME), "build\n");
+        fs::create_dir_all(top.join("build")).unwrap();
+
+        let files = IgnoreFiles::new(GITIGNORE_FILE_NAME);
+
+        assert!(files
+            .evaluate(&top.join("build"), temp.path())
+            .unwrap()
+            .is_some());
+        assert_eq!(None, files.evaluate(&top.join("build"), &top).unwrap());
+    }
+
+    #[test]
+    fn reloads_after_invalidation() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("build");
+        arrange_file(&temp.path().join(DROPIGNORE_FILE_NAME), "target\n");
+
+        let files = IgnoreFiles::new(DROPIGNORE_FILE_NAME);
+        assert_eq!(None, files.evaluate(&path, temp.path()).unwrap());
+
+        arrange_file(&temp.path().join(DROPIGNORE_FILE_NAME), "build\n");
+        assert_eq!(None, files.evaluate(&path, temp.path()).unwrap());
+
+        files.invalidate(temp.path());
+        assert_eq!(
+            Some(FileMatch {
+                ignore: true,
+                file: temp.path().join(DROPIGNORE_FILE_NAME),
+                pattern: String::from("build"),
+            }),
+            files.evaluate(&path, temp.path()).unwrap()
+        );
+    }
+
+    fn arrange_file(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::backend::IgnoreBackend;
+use crate::error::Error;
+
+const SECTION_BEGIN: &str = "# BEGIN dropignore (managed section, do not edit)";
+const SECTION_END: &str = "# END dropignore";
+
+/// The ignore file flavor of a sync client.
+pub trait IgnoreListFormat {
+    /// The name of the ignore file located at the root of the synced folder
+    const FILE_NAME: &'static str;
+
+    /// Creates the pattern for the given `/`-separated path relative to the synced folder.
+    fn pattern(relative_path: &str) -> String;
+
+    /// Whether a pattern can be created for the path at all, i.e. matching nothing but the path.
+    fn is_representable(_relative_path: &str) -> bool {
+        true
+    }
+}
+
+/// Maintains a dropignore managed section within the ignore file at the root of a synced folder.
+///
+/// Any lines outside of the section are left untouched and are considered foreign ignores.
+pub struct IgnoreList<F: IgnoreListFormat> {
+    root: PathBuf,
+    file: PathBuf,
+    // the parsed file together with its modification time
+    cache: RefCell<Option<(Option<SystemTime>, Lines)>>,
+    format: PhantomData<F>,
+}
+
+#[derive(Clone, Default)]
+struct Lines {
+    foreign: Vec<String>,
+    managed: Vec<String>,
+}
+
+impl<F: IgnoreListFormat> IgnoreList<F> {
+    pub fn new(root: &Path) -> Self {
+        IgnoreList {
+            root: root.to_path_buf(),
+            file: root.join(F::FILE_NAME),
+            cache: RefCell::new(None),
+            format: PhantomData,
+        }
+    }
+
+    fn pattern(&self, path: &Path) -> Result<String, Error> {
+        let components: Option<Vec<&str>> = path
+            .strip_prefix(&self.root)
+            .ok()
+            .and_then(|p| p.components().map(|c| c.as_os_str().to_str()).collect());
+
+        match components.map(|c| c.join("/")) {
+            Some(p) if !p.is_empty() && !p.contains('\n') && F::is_representable(&p) => {
+                Ok(F::pattern(&p))
+            }
+            _ => Err(Error::Unrepresentable {
+                path: path.to_path_buf(),
+                file: self.file.clone(),
+            }),
+        }
+    }
+
+    fn read(&self) -> Result<Lines, Error> {
+        let modified = fs::metadata(&self.file).and_then(|m| m.modified()).ok();
+
+        if let Some((cached_modified, lines)) = self.cache.borrow().as_ref() {
+            if modified.is_some() && *cached_modified == modified {
+                return Ok(lines.clone());
+            }
+        }
+
+        let content = match fs::read_to_string(&self.file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::io(&self.file, e)),
+        };
+
+        let mut lines = Lines::default();
+        let mut in_section = false;
+        for line in content.lines() {
+            match line {
+                SECTION_BEGIN => in_section = true,
+                SECTION_END => in_section = false,
+                l if in_section => lines.managed.push(l.to_string()),
+                l => lines.foreign.push(l.to_string()),
+            }
+        }
+
+        self.cache.replace(Some((modified, lines.clone())));
+
+        Ok(lines)
+    }
+
+    fn write(&self, lines: &Lines) -> Result<(), Error> {
+        let mut content = lines.foreign.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+
+        if !lines.managed.is_empty() {
+            content.push_str(SECTION_BEGIN);
+            content.push('\n');
+            for line in &lines.managed {
+                content.push_str(line);
+                content.push('\n');
+            }
+            content.push_str(SECTION_END);
+            content.push('\n');
+        }
+
+        // replace atomically, the sync client might read the file any time
+        let temp_file = self
+            .file
+            .with_file_name(format!("{}.dropignore", F::FILE_NAME));
+        fs::write(&temp_file, content).map_err(|e| Error::io(&temp_file, e))?;
+        fs::rename(&temp_file, &self.file).map_err(|e| Error::io(&self.file, e))?;
+
+        self.cache.replace(None);
+
+        Ok(())
+    }
+
+    fn update(&self, path: &Path, f: impl FnOnce(&mut Lines, String)) -> Result<(), Error> {
+        let pattern = self.pattern(path)?;
+        let mut lines = self.read()?;
+
+        f(&mut lines, pattern);
+
+        self.write(&lines)
+    }
+}
+
+impl<F: IgnoreListFormat> IgnoreBackend for IgnoreList<F> {
+    fn is_ignored(&self, path: &Path) -> Result<bool, Error> {
+        let pattern = self.pattern(path)?;
+        let lines = self.read()?;
+
+        Ok(lines.managed.contains(&pattern) || lines.foreign.contains(&pattern))
+    }
+
+    fn ignore(&self, path: &Path) -> Result<(), Error> {
+        self.update(path, |lines, pattern| {
+            if !lines.managed.contains(&pattern) {
+                lines.managed.push(pattern);
+            }
+        })
+    }
+
+    fn unignore(&self, path: &Path) -> Result<(), Error> {
+        self.update(path, |lines, pattern| {
+            lines.managed.retain(|l| *l != pattern);
+            lines.foreign.retain(|l| *l != pattern);
+        })
+    }
+
+    fn is_managed(&self, path: &Path) -> Result<bool, Error> {
+        let pattern = self.pattern(path)?;
+
+        Ok(self.read()?.managed.contains(&pattern))
+    }
+
+    fn manage(&self, path: &Path) -> Result<bool, Error> {
+        if !self.is_ignored(path)? {
+            return Ok(false);
+        }
+
+        self.update(path, |lines, pattern| {
+            lines.foreign.retain(|l| *l != pattern);
+            if !lines.managed.contains(&pattern) {
+                lines.managed.push(pattern);
+            }
+        })?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    struct TestFormat;
+
+    impl IgnoreListFormat for TestFormat {
+        const FILE_NAME: &'static str = ".testignore";
+
+        fn pattern(relative_path: &str) -> String {
+            format!("/{}", relative_path)
+        }
+    }
+
+    #[test]
+    fn ignore_and_unignore() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("foo/build");
+        let ignore_list = IgnoreList::<TestFormat>::new(temp.path());
+
+        assert!(!ignore_list.is_ignored(&path).unwrap());
+
+        ignore_list.ignore(&path).unwrap();
+
+        assert!(ignore_list.is_ignored(&path).unwrap());
+        assert!(ignore_list.is_managed(&path).unwrap());
+        assert_eq!(
+            format!("{}\n/foo/build\n{}\n", SECTION_BEGIN, SECTION_END),
+            read_ignore_file(&temp)
+        );
+
+        ignore_list.unignore(&path).unwrap();
+
+        assert!(!ignore_list.is_ignored(&path).unwrap());
+        assert_eq!("", read_ignore_file(&temp));
+    }
+
+    #[test]
+    fn keeps_foreign_lines() {
+        let temp = TempDir::new().unwrap();
+        arrange_ignore_file(&temp, "# mine\n/foo/target\n");
+        let ignore_list = IgnoreList::<TestFormat>::new(temp.path());
+
+        ignore_list.ignore(&temp.path().join("foo/build")).unwrap();
+
+        assert!(ignore_list
+            .is_ignored(&temp.path().join("foo/target"))
+            .unwrap());
+        assert!(!ignore_list
+            .is_managed(&temp.path().join("foo/target"))
+            .unwrap());
+        assert_eq!(
+            format!(
+                "# mine\n/foo/target\n{}\n/foo/build\n{}\n",
+                SECTION_BEGIN, SECTION_END
+            ),
+            read_ignore_file(&temp)
+        );
+    }
+
+    #[test]
+    fn manage_foreign_ignore() {
+        let temp = TempDir::new().unwrap();
+        arrange_ignore_file(&temp, "/foo/target\n");
+        let path = temp.path().join("foo/target");
+        let ignore_list = IgnoreList::<TestFormat>::new(temp.path());
+
+        assert!(ignore_list.manage(&path).unwrap());
+
+        assert!(ignore_list.is_managed(&path).unwrap());
+        assert!(!ignore_list.manage(&temp.path().join("foo/build")).unwrap());
+        assert_eq!(
+            format!("{}\n/foo/target\n{}\n", SECTION_BEGIN, SECTION_END),
+            read_ignore_file(&temp)
+        );
+    }
+
+    #[test]
+    fn rejects_paths_outside_of_root() {
+        let temp = TempDir::new().unwrap();
+        let ignore_list = IgnoreList::<TestFormat>::new(&temp.path().join("root"));
+
+        assert!(ignore_list.ignore(&temp.path().join("foo/build")).is_err());
+        assert!(ignore_list
+            .is_ignored(&temp.path().join("foo/build"))
+            .is_err());
+    }
+
+    fn arrange_ignore_file(temp: &TempDir, content: &str) {
+        fs::write(temp.path().join(TestFormat::FILE_NAME), content).unwrap();
+    }
+
+    fn read_ignore_file(temp: &TempDir) -> String {
+        fs::read_to_string(temp.path().join(TestFormat::FILE_NAME)).unwrap()
+    }
+}
@@ -0,0 +1,43 @@
+use crate::ignore_list::IgnoreListFormat;
+
+/// The `.mignore` file of the Maestral Dropbox client using gitignore syntax.
+pub struct Maestral;
+
+impl IgnoreListFormat for Maestral {
+    const FILE_NAME: &'static str = ".mignore";
+
+    fn pattern(relative_path: &str) -> String {
+        let mut pattern = String::from("/");
+        for c in relative_path.chars() {
+            if matches!(c, '\\' | '*' | '?' | '[' | ']') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+
+        // trailing spaces are stripped unless escaped
+        if pattern.ends_with(' ') {
+            pattern.pop();
+            pattern.push_str("\\ ");
+        }
+
+        pattern
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        relative_path,
+        expected,
+        case::plain("foo/build", "/foo/build"),
+        case::glob_characters("foo/[a]*?", "/foo/\\[a\\]\\*\\?"),
+        case::trailing_space("foo/build ", "/foo/build\\ ")
+    )]
+    fn pattern(relative_path: &str, expected: &str) {
+        assert_eq!(expected, Maestral::pattern(relative_path));
+    }
+}
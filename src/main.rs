@@ -1,18 +1,39 @@
+mod backend;
+mod check;
 mod configuration;
+mod conflict;
+mod diagnostics;
 mod dropbox;
+mod dropbox_info;
+mod error;
+mod git;
+mod ignore_file;
+mod ignore_list;
+mod maestral;
 mod matcher;
+mod metrics;
+mod nextcloud;
+mod presets;
 mod scanner;
+mod syncthing;
 
 use std::env;
 use std::io;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::thread;
 
-use clap::{crate_description, crate_name, crate_version, App, AppSettings, Arg, SubCommand};
+use clap::{
+    crate_description, crate_name, crate_version, App, AppSettings, Arg, ArgMatches, SubCommand,
+};
 use fern::colors::{Color, ColoredLevelConfig};
-use log::{error, LevelFilter};
+use log::{error, info, warn, LevelFilter};
 
+use crate::check::Severity;
 use crate::configuration::Configuration;
-use crate::dropbox::Dropbox;
+use crate::dropbox_info::Account;
+use crate::error::Error;
+use crate::git::GitExcludes;
 use crate::matcher::Matcher;
 use crate::scanner::Scanner;
 
@@ -22,6 +43,20 @@ fn main() {
         .short("n")
         .long("dry-run");
 
+    let reconcile_arg = Arg::with_name("reconcile")
+        .help("Also unignores entries previously ignored by dropignore which are not matching anymore, including the ones within ignored folders and dot-directories. (default: false)")
+        .short("r")
+        .long("reconcile");
+
+    let account_arg = Arg::with_name("account")
+        .help("The Dropbox account(s) whose root folders are used when no directory is given. (default: all)")
+        .short("a")
+        .long("account")
+        .takes_value(true)
+        .possible_values(&Account::VARIANTS);
+
+    let preset_names: Vec<&str> = presets::PRESETS.iter().map(|p| p.name).collect();
+
     let app = App::new(crate_name!())
         .version(crate_version!())
         .about(crate_description!())
@@ -39,18 +74,147 @@ fn main() {
             SubCommand::with_name("scan")
                 .about("Scans the given directory recursively for ignore candidates")
                 .arg(&dry_run_arg)
+                .arg(&reconcile_arg)
+                .arg(&account_arg)
                 .arg(
                     Arg::with_name("directory")
-                        .help("The directory to scan (default: current working directory)"),
+                        .help("The directory to scan (default: Dropbox root folders, otherwise current working directory)"),
                 ),
         )
         .subcommand(
             SubCommand::with_name("watch")
                 .about("Watches the given directory recursively for ignore candidates")
                 .arg(&dry_run_arg)
+                .arg(&reconcile_arg)
+                .arg(&account_arg)
+                .arg(
+                    Arg::with_name("directory")
+                        .help("The directory to watch (default: Dropbox root folders, otherwise current working directory)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .alias("status")
+                .about("Lists all ignored entries of the given directory recursively")
+                .arg(&account_arg)
+                .arg(
+                    Arg::with_name("directory")
+                        .help("The directory to list (default: Dropbox root folders, otherwise current working directory)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("orphans")
+                .about("Reports ignored entries of the given directory recursively which no ignore-spec accounts for")
+                .arg(&dry_run_arg)
+                .arg(
+                    Arg::with_name("unignore")
+                        .help("Also unignores the reported entries at or below the given path, e.g. an orphan or the parent directory of a group (may be repeated)")
+                        .short("u")
+                        .long("unignore")
+                        .takes_value(true)
+                        .value_name("path")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(&account_arg)
+                .arg(
+                    Arg::with_name("directory")
+                        .help("The directory to scan (default: Dropbox root folders, otherwise current working directory)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("conflicts")
+                .about("Reports \"(Selective Sync Conflict)\" copies of the given directory recursively and optionally deletes or merges them back")
+                .arg(&dry_run_arg)
+                .arg(
+                    Arg::with_name("delete")
+                        .help("Deletes copies whose original matches an ignore-spec (default: false)")
+                        .short("d")
+                        .long("delete"),
+                )
+                .arg(
+                    Arg::with_name("merge")
+                        .help("Merges any other copies back into their original (default: false)")
+                        .short("m")
+                        .long("merge"),
+                )
+                .arg(&account_arg)
+                .arg(
+                    Arg::with_name("directory")
+                        .help("The directory to clean up (default: Dropbox root folders, otherwise current working directory)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("repair")
+                .about("Repairs partially applied or invalid ignore markers of the given directory recursively")
+                .arg(&dry_run_arg)
+                .arg(&account_arg)
+                .arg(
+                    Arg::with_name("directory")
+                        .help("The directory to repair (default: Dropbox root folders, otherwise current working directory)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Explains why the given paths are ignored or not")
+                .arg(&account_arg)
                 .arg(
                     Arg::with_name("directory")
-                        .help("The directory to watch (default: current working directory)"),
+                        .help("The scanned directory patterns are anchored to (default: Dropbox root folders, otherwise current working directory)")
+                        .short("d")
+                        .long("directory")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("paths")
+                        .help("The paths to explain")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks the configuration for invalid specs and specs without any effect")
+                .arg(&account_arg)
+                .arg(
+                    Arg::with_name("directory")
+                        .help("The directory specs must not match itself (default: Dropbox root folders, otherwise current working directory)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("presets")
+                .about("Lists the built-in presets or shows the specs of one")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("list").about("Lists the built-in presets"))
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Shows the specs the given preset expands to")
+                        .arg(
+                            Arg::with_name("name")
+                                .help("The name of the preset")
+                                .required(true)
+                                .possible_values(&preset_names),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("unignore")
+                .about("Removes the ignore marker from the given paths")
+                .arg(&dry_run_arg)
+                .arg(
+                    Arg::with_name("matching")
+                        .help("Unignores every entry below the given directories matching an ignore-spec")
+                        .short("m")
+                        .long("matching"),
+                )
+                .arg(&account_arg)
+                .arg(
+                    Arg::with_name("paths")
+                        .help("The paths to unignore or the directories to scan when using '--matching' (default: Dropbox root folders, otherwise current working directory)")
+                        .multiple(true)
+                        .required_unless("matching"),
                 ),
         );
 
@@ -58,36 +222,218 @@ fn main() {
 
     init_logging(matches.occurrences_of("verbose"));
 
-    let configuration = Configuration::load(crate_name!()).unwrap();
+    let configuration = exit_on_error(Configuration::load(crate_name!()));
 
     match matches.subcommand() {
         ("scan", Some(subcommand_matches)) | ("watch", Some(subcommand_matches)) => {
-            let directory = match subcommand_matches.value_of("directory") {
-                Some(d) => Ok(std::path::PathBuf::from(d)),
-                _ => env::current_dir(),
-            };
-
-            if directory.is_err() {
-                error!(
-                    "Couldn't determine directory to scan: {:?}",
-                    directory.err().unwrap()
+            let directories = directories_arg(subcommand_matches, "directory");
+
+            let dry_run = subcommand_matches.is_present("dry-run");
+            let reconcile = subcommand_matches.is_present("reconcile");
+            let watch = matches.subcommand_name().map(|n| n == "watch").unwrap();
+
+            if watch {
+                // every directory is watched by its own scanner
+                thread::scope(|s| {
+                    for directory in directories {
+                        let configuration = &configuration;
+                        s.spawn(move || {
+                            exit_on_error(
+                                scanner(configuration, &directory)
+                                    .and_then(|s| s.scan(directory, watch, dry_run, reconcile)),
+                            )
+                        });
+                    }
+                });
+            } else {
+                for directory in directories {
+                    exit_on_error(
+                        scanner(&configuration, &directory)
+                            .and_then(|s| s.scan(directory, watch, dry_run, reconcile)),
+                    );
+                }
+            }
+        }
+        ("list", Some(subcommand_matches)) => {
+            for directory in directories_arg(subcommand_matches, "directory") {
+                exit_on_error(scanner(&configuration, &directory).and_then(|s| s.list(directory)));
+            }
+        }
+        ("orphans", Some(subcommand_matches)) => {
+            let dry_run = subcommand_matches.is_present("dry-run");
+            let unignore: Vec<PathBuf> = subcommand_matches
+                .values_of("unignore")
+                .map(|values| values.map(PathBuf::from).collect())
+                .unwrap_or_default();
+
+            for directory in directories_arg(subcommand_matches, "directory") {
+                exit_on_error(
+                    scanner(&configuration, &directory)
+                        .and_then(|s| s.orphans(directory, &unignore, dry_run)),
                 );
-                process::exit(1);
             }
+        }
+        ("conflicts", Some(subcommand_matches)) => {
+            let dry_run = subcommand_matches.is_present("dry-run");
+            let delete = subcommand_matches.is_present("delete");
+            let merge = subcommand_matches.is_present("merge");
 
-            let matcher = Matcher::new(&configuration.matcher_config).unwrap();
-            let dropbox = Dropbox::new().unwrap();
-            let scanner = Scanner::new(matcher, dropbox).unwrap();
+            for directory in directories_arg(subcommand_matches, "directory") {
+                exit_on_error(
+                    scanner(&configuration, &directory)
+                        .and_then(|s| s.conflicts(directory, delete, merge, dry_run)),
+                );
+            }
+        }
+        ("repair", Some(subcommand_matches)) => {
+            let dry_run = subcommand_matches.is_present("dry-run");
 
+            for directory in directories_arg(subcommand_matches, "directory") {
+                exit_on_error(
+                    scanner(&configuration, &directory).and_then(|s| s.repair(directory, dry_run)),
+                );
+            }
+        }
+        ("unignore", Some(subcommand_matches)) => {
             let dry_run = subcommand_matches.is_present("dry-run");
-            let watch = matches.subcommand_name().map(|n| n == "watch").unwrap();
 
-            scanner.scan(directory.unwrap(), watch, dry_run);
+            if subcommand_matches.is_present("matching") {
+                for directory in directories_arg(subcommand_matches, "paths") {
+                    exit_on_error(
+                        scanner(&configuration, &directory)
+                            .and_then(|s| s.unignore_matching(directory, dry_run)),
+                    );
+                }
+            } else {
+                let paths = subcommand_matches.values_of("paths").unwrap();
+                for path in paths.map(PathBuf::from) {
+                    exit_on_error(
+                        scanner(&configuration, &path).and_then(|s| s.unignore(&[path], dry_run)),
+                    );
+                }
+            }
+        }
+        ("explain", Some(subcommand_matches)) => {
+            let directories = directories_arg(subcommand_matches, "directory");
+
+            for path in subcommand_matches
+                .values_of("paths")
+                .unwrap()
+                .map(Path::new)
+            {
+                exit_on_error(
+                    scanner(&configuration, path).and_then(|s| s.explain(&directories, path)),
+                );
+            }
+        }
+        ("check", Some(subcommand_matches)) => {
+            let directories = directories_arg(subcommand_matches, "directory");
+            let findings = check::check(&configuration.matcher_config, &directories);
+
+            for finding in &findings {
+                match finding.severity {
+                    Severity::Error => error!("{}", finding),
+                    Severity::Warning => warn!("{}", finding),
+                }
+            }
+
+            let errors = findings
+                .iter()
+                .filter(|f| f.severity == Severity::Error)
+                .count();
+            if findings.is_empty() {
+                info!("No problems found.");
+            } else {
+                info!(
+                    "Found {} errors and {} warnings.",
+                    errors,
+                    findings.len() - errors
+                );
+            }
+            if errors > 0 {
+                process::exit(1);
+            }
         }
+        ("presets", Some(subcommand_matches)) => match subcommand_matches.subcommand() {
+            ("list", _) => {
+                for preset in presets::PRESETS {
+                    println!("{:8}{}", preset.name, preset.description);
+                }
+            }
+            ("show", Some(show_matches)) => {
+                let name = show_matches.value_of("name").unwrap();
+                let preset = presets::find(name).unwrap();
+                print!("# {}\n{}", preset.description, preset.specs);
+            }
+            _ => unreachable!(),
+        },
         _ => unreachable!(),
     }
 }
 
+fn scanner(configuration: &Configuration, path: &Path) -> Result<Scanner, Error> {
+    let matcher = Matcher::new(&configuration.matcher_config)?;
+    let backend = backend::for_path(&configuration.roots, path)?;
+
+    let mut scanner = Scanner::new(matcher, backend)?;
+    if let Some(root) = backend::root_for_path(&configuration.roots, path) {
+        scanner = scanner.with_scope_root(root);
+    }
+
+    match &configuration.gitignore {
+        Some(gitignore_configs) => {
+            let directories: Vec<PathBuf> =
+                gitignore_configs.iter().map(|gc| gc.path.clone()).collect();
+            Ok(scanner.with_git_excludes(GitExcludes::new(&directories)))
+        }
+        None => Ok(scanner),
+    }
+}
+
+fn exit_on_error<T>(result: Result<T, Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn directories_arg(subcommand_matches: &ArgMatches, name: &str) -> Vec<PathBuf> {
+    if let Some(values) = subcommand_matches.values_of(name) {
+        return values.map(PathBuf::from).collect();
+    }
+
+    let account = subcommand_matches.value_of("account");
+
+    match (dropbox_info::info_file_path(), account) {
+        (Some(info_file_path), _) => {
+            let account = account.unwrap_or("all").parse().unwrap();
+            match dropbox_info::roots(&info_file_path, account) {
+                Ok(roots) => return roots,
+                Err(e) => {
+                    error!("Couldn't determine Dropbox root folders: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        (None, Some(_)) => {
+            error!("Couldn't determine Dropbox root folders: no info.json found");
+            process::exit(1);
+        }
+        (None, None) => {}
+    }
+
+    match env::current_dir() {
+        Ok(d) => vec![d],
+        Err(e) => {
+            error!("Couldn't determine directory to scan: {:?}", e);
+            process::exit(1);
+        }
+    }
+}
+
 fn init_logging(verbosity: u64) {
     let colors = ColoredLevelConfig::new()
         .trace(Color::Magenta)
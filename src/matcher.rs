@@ -1,72 +1,1009 @@
-use globset::{Error, Glob, GlobSet, GlobSetBuilder};
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-use crate::configuration::{MatcherConfig, MatcherSpec};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use log::warn;
+use regex::{RegexBuilder, RegexSet, RegexSetBuilder};
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization};
 
+use crate::configuration::{
+    EntryType, MatcherConfig, MatcherSpec, Normalization, PresetConfig, RuleAction,
+};
+use crate::error::Error;
+use crate::metrics::MetricsCache;
+
+#[derive(Debug, PartialEq)]
+pub enum MatchResult {
+    /// matching an ignore-spec and no skip-spec
+    Ignore,
+    /// matching an ignore-spec but overridden by a skip-spec
+    Skip,
+    /// not matching any ignore-spec
+    NoMatch,
+}
+
+/// The details of evaluating a candidate, explaining its result.
+#[derive(Debug, PartialEq)]
+pub struct MatchReport<'a> {
+    pub result: MatchResult,
+    /// every matching ignore-spec and ignoring rule
+    pub ignore_specs: Vec<SpecRef<'a>>,
+    /// every matching skip-spec and skipping rule, skip-specs are only evaluated with a matching
+    /// ignore-spec
+    pub skip_specs: Vec<SpecRef<'a>>,
+    /// the last matching rule of the deciding scope or the global configuration
+    pub rule: Option<SpecRef<'a>>,
+    /// the innermost scope with a matching spec, `None` if the global configuration decided
+    pub scope: Option<&'a str>,
+}
+
+/// A spec of the configuration, referred to by the list it is part of and its index there.
+#[derive(Debug, PartialEq)]
+pub struct SpecRef<'a> {
+    pub specs: &'a str,
+    pub index: usize,
+    pub pattern: &'a str,
+}
+
+impl fmt::Display for SpecRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}] {:?}", self.specs, self.index, self.pattern)
+    }
+}
+
+/// An entry to be matched, in all of the forms the specs can refer to.
+///
+/// Glob patterns match the path bytewise, so non-UTF-8 names are matched as they are, while the
+/// relative path and regular expressions use a lossy representation of them.
+pub struct Candidate<'a> {
+    // the entry on the filesystem, e.g. for checking markers
+    path: &'a Path,
+    // the path matched by the specs, only differing from `path` if normalized
+    matched_path: Cow<'a, Path>,
+    // relative to the scanned root, always starting with a `/`
+    relative_path: String,
+    // relative to the root folder scope paths refer to, `None` if that's the scanned root
+    scope_relative_path: Option<String>,
+    file_name: Cow<'a, Path>,
+    // relative to the scanned root, which has depth `0`
+    depth: usize,
+    // `None` if unknown, which only matches specs for any type
+    entry_type: Option<EntryType>,
+    // `None` if unavailable, which never matches specs with metric predicates
+    metrics: Option<&'a MetricsCache>,
+}
+
+impl<'a> Candidate<'a> {
+    pub fn new<R, P>(root: &R, path: &'a P) -> Self
+    where
+        R: AsRef<Path> + ?Sized,
+        P: AsRef<Path> + ?Sized,
+    {
+        let path = path.as_ref();
+        // only strips whole components, paths outside of the root are taken as they are
+        let names = relative_names(root.as_ref(), path);
+
+        Candidate {
+            path,
+            matched_path: Cow::Borrowed(path),
+            relative_path: format!("/{}", names.join("/")),
+            scope_relative_path: None,
+            file_name: Cow::Borrowed(path.file_name().map(Path::new).unwrap_or(path)),
+            depth: names.len(),
+            entry_type: None,
+            metrics: None,
+        }
+    }
+
+    pub fn with_entry_type(mut self, entry_type: EntryType) -> Self {
+        self.entry_type = Some(entry_type);
+        self
+    }
+
+    /// Resolves scope paths against the given root folder (e.g. the Dropbox folder) instead of
+    /// the scanned root, if the entry lies within it.
+    pub fn with_scope_root(mut self, scope_root: &Path) -> Self {
+        if self.path.starts_with(scope_root) {
+            let names = relative_names(scope_root, self.path);
+            self.scope_relative_path = Some(format!("/{}", names.join("/")));
+        }
+        self
+    }
+
+    /// Returns the candidate relative to the path of a scope (e.g. `/Clients`), if it lies
+    /// within it.
+    fn scoped(&self, base: &str) -> Option<Candidate<'a>> {
+        let scope_relative_path = self
+            .scope_relative_path
+            .as_deref()
+            .unwrap_or(&self.relative_path);
+        let rest = scope_relative_path.strip_prefix(base)?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+
+        let relative_path = format!("/{}", rest.trim_start_matches('/'));
+        let depth = relative_path.split('/').filter(|s| !s.is_empty()).count();
+
+        Some(Candidate {
+            path: self.path,
+            matched_path: self.matched_path.clone(),
+            relative_path,
+            scope_relative_path: None,
+            file_name: self.file_name.clone(),
+            depth,
+            entry_type: self.entry_type,
+            metrics: self.metrics,
+        })
+    }
+
+    /// Returns the candidate with all matched forms in the given normal form. Non-UTF-8 paths
+    /// are only normalized in their lossy relative form.
+    fn normalized(&self, form: Normalization) -> Candidate<'a> {
+        let normalize_path = |path: &Cow<'a, Path>| match path.to_str().map(|p| normalize(form, p))
+        {
+            Some(Cow::Owned(normalized)) => Cow::Owned(PathBuf::from(normalized)),
+            _ => path.clone(),
+        };
+
+        Candidate {
+            path: self.path,
+            matched_path: normalize_path(&self.matched_path),
+            relative_path: normalize(form, &self.relative_path).into_owned(),
+            scope_relative_path: self
+                .scope_relative_path
+                .as_ref()
+                .map(|p| normalize(form, p).into_owned()),
+            file_name: normalize_path(&self.file_name),
+            depth: self.depth,
+            entry_type: self.entry_type,
+            metrics: self.metrics,
+        }
+    }
+
+    /// Makes the metrics of the entry available, only measuring it when a spec needs them.
+    pub fn with_metrics(mut self, metrics: &'a MetricsCache) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+}
+
+/// Returns the names of the components of the path below the root, all of them if the path lies
+/// outside of it.
+fn relative_names<'p>(root: &Path, path: &'p Path) -> Vec<Cow<'p, str>> {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Evaluates the global specs and then the specs of every scope containing the candidate on top
+/// of them, from the outermost to the innermost scope.
 pub struct Matcher {
-    ignore_globset: GlobSet,
-    skip_globset: GlobSet,
+    global: Layer,
+    // ordered by depth, so inner scopes are evaluated last
+    scopes: Vec<Scope>,
+    // the normal form of the patterns, candidates are brought into it before matching
+    normalization: Option<Normalization>,
+}
+
+/// A layer of specs only applying below a path relative to the root folder, e.g. the Dropbox
+/// folder, or the scanned root outside of one.
+struct Scope {
+    // e.g. `/Clients`, always starting with a `/`
+    path: String,
+    // e.g. `scopes[0] "Clients"`
+    label: String,
+    layer: Layer,
+}
+
+/// The ignore-specs and skip-specs, where a matching skip-spec always wins, and then the rules on
+/// top of them, where the last matching rule wins.
+struct Layer {
+    ignore_specs: SpecSet,
+    skip_specs: SpecSet,
+    rules: SpecSet,
+    rule_actions: Vec<RuleAction>,
 }
 
 impl Matcher {
-    pub fn new(matcher_config: &Option<MatcherConfig>) -> Result<Self, String> {
-        let mut ignore_spec_builder = GlobSetBuilder::new();
-        let mut skip_spec_builder = GlobSetBuilder::new();
-
-        if let Some(mc) = matcher_config {
-            if let Some(ignore_specs) = &mc.ignore_specs {
-                let build_result = build_globset(&mut ignore_spec_builder, ignore_specs);
-                if build_result.is_err() {
-                    return Err(build_result.err().unwrap().to_string());
+    pub fn new(matcher_config: &Option<MatcherConfig>) -> Result<Self, Error> {
+        let matcher_config = match matcher_config {
+            Some(mc) => mc,
+            None => {
+                return Ok(Self {
+                    global: Layer::new(&None, &None, &None, "", &[])?,
+                    scopes: vec![],
+                    normalization: None,
+                })
+            }
+        };
+        let normalized = matcher_config
+            .unicode_normalization
+            .map(|form| normalized_config(matcher_config, form));
+        let matcher_config = normalized.as_ref().unwrap_or(matcher_config);
+
+        let mut scopes = Vec::new();
+        for (index, scope_config) in matcher_config.scopes.iter().flatten().enumerate() {
+            let name = format!("scopes[{}]", index);
+            scopes.push(Scope {
+                path: scope_path(&scope_config.path),
+                label: format!("{} {:?}", name, scope_config.path),
+                layer: Layer::new(
+                    &scope_config.ignore_specs,
+                    &scope_config.skip_specs,
+                    &scope_config.rules,
+                    &format!("{}.", name),
+                    &[],
+                )?,
+            });
+        }
+        scopes.sort_by_key(|s| s.path.matches('/').count());
+
+        let matcher = Self {
+            global: Layer::new(
+                &matcher_config.ignore_specs,
+                &matcher_config.skip_specs,
+                &matcher_config.rules,
+                "",
+                &matcher_config.presets,
+            )?,
+            scopes,
+            normalization: matcher_config.unicode_normalization,
+        };
+
+        Ok(matcher)
+    }
+
+    pub fn evaluate(&self, candidate: &Candidate) -> MatchResult {
+        let normalized = self.normalization.map(|form| candidate.normalized(form));
+        let candidate = normalized.as_ref().unwrap_or(candidate);
+
+        let mut result = self.global.evaluate(MatchResult::NoMatch, candidate);
+
+        for scope in &self.scopes {
+            if let Some(scoped) = candidate.scoped(&scope.path) {
+                result = scope.layer.evaluate(result, &scoped);
+            }
+        }
+
+        result
+    }
+
+    /// Evaluates the candidate like `evaluate`, but reports all specs involved.
+    pub fn report(&self, candidate: &Candidate) -> MatchReport<'_> {
+        let normalized = self.normalization.map(|form| candidate.normalized(form));
+        let candidate = normalized.as_ref().unwrap_or(candidate);
+        let mut report = MatchReport {
+            result: MatchResult::NoMatch,
+            ignore_specs: vec![],
+            skip_specs: vec![],
+            rule: None,
+            scope: None,
+        };
+
+        self.global.report(candidate, &mut report);
+        for scope in &self.scopes {
+            if let Some(scoped) = candidate.scoped(&scope.path) {
+                if scope.layer.report(&scoped, &mut report) {
+                    report.scope = Some(&scope.label);
                 }
             }
+        }
+
+        report
+    }
+
+    fn layers(&self) -> impl Iterator<Item = &Layer> {
+        std::iter::once(&self.global).chain(self.scopes.iter().map(|s| &s.layer))
+    }
+
+    /// Whether any spec is restricted to an entry type, i.e. candidates need to provide it.
+    pub fn needs_entry_type(&self) -> bool {
+        self.layers()
+            .any(|l| l.spec_sets().iter().any(|s| s.needs_entry_type()))
+    }
+
+    /// Whether any spec has metric predicates, i.e. candidates need to provide metrics.
+    pub fn needs_metrics(&self) -> bool {
+        self.layers()
+            .any(|l| l.spec_sets().iter().any(|s| s.needs_metrics()))
+    }
+
+    /// Whether the path is a marker any spec is conditional on, i.e. its appearance may change the
+    /// result for the entries around it.
+    pub fn is_marker(&self, path: &Path) -> bool {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| {
+                self.layers()
+                    .any(|l| l.spec_sets().iter().any(|s| s.is_marker(n)))
+            })
+            .unwrap_or(false)
+    }
 
-            if let Some(skip_specs) = &mc.skip_specs {
-                let build_result = build_globset(&mut skip_spec_builder, skip_specs);
-                if build_result.is_err() {
-                    return Err(build_result.err().unwrap().to_string());
+    /// Returns the spec keeping the candidate from being ignored, if any, as decided by the
+    /// innermost scope with a matching spec: the last matching rule if it is a skipping one,
+    /// otherwise the first matching skip-spec without any matching rule.
+    pub fn skip_spec(&self, candidate: &Candidate) -> Option<SpecRef<'_>> {
+        let normalized = self.normalization.map(|form| candidate.normalized(form));
+        let candidate = normalized.as_ref().unwrap_or(candidate);
+        for scope in self.scopes.iter().rev() {
+            if let Some(scoped) = candidate.scoped(&scope.path) {
+                if let Some(decision) = scope.layer.skip_decision(&scoped) {
+                    return decision;
                 }
             }
         }
 
-        let matcher = Self {
-            ignore_globset: ignore_spec_builder.build().unwrap(),
-            skip_globset: skip_spec_builder.build().unwrap(),
+        self.global.skip_decision(candidate).flatten()
+    }
+}
+
+impl Layer {
+    /// Builds the layer of the given specs, the ones of presets are evaluated as if they were
+    /// appended to them.
+    fn new(
+        ignore_specs: &Option<Vec<MatcherSpec>>,
+        skip_specs: &Option<Vec<MatcherSpec>>,
+        rules: &Option<Vec<MatcherSpec>>,
+        prefix: &str,
+        presets: &[PresetConfig],
+    ) -> Result<Self, Error> {
+        let empty_specs = Vec::new();
+        let mut ignore_lists = vec![(
+            format!("{}ignore-specs", prefix),
+            ignore_specs.as_deref().unwrap_or_default(),
+        )];
+        let mut skip_lists = vec![(
+            format!("{}skip-specs", prefix),
+            skip_specs.as_deref().unwrap_or_default(),
+        )];
+        for preset in presets {
+            ignore_lists.push((
+                format!("presets[{}].ignore-specs", preset.name),
+                preset.ignore_specs.as_deref().unwrap_or_default(),
+            ));
+            skip_lists.push((
+                format!("presets[{}].skip-specs", preset.name),
+                preset.skip_specs.as_deref().unwrap_or_default(),
+            ));
+        }
+        reject_rule_options(&ignore_lists)?;
+        reject_rule_options(&skip_lists)?;
+        let rules = rules.as_ref().unwrap_or(&empty_specs);
+
+        // a leading `!` negates a rule like in gitignore files
+        let (negated_rules, rule_actions): (Vec<MatcherSpec>, Vec<RuleAction>) = rules
+            .iter()
+            .map(|rule| match rule.pattern.strip_prefix('!') {
+                Some(pattern) => (
+                    MatcherSpec {
+                        pattern: pattern.to_string(),
+                        ..rule.clone()
+                    },
+                    RuleAction::Skip,
+                ),
+                None => (rule.clone(), rule.action),
+            })
+            .unzip();
+        let mut rule_set = SpecSet::new(&negated_rules, &format!("{}rules", prefix))?;
+        rule_set.patterns = rules.iter().map(|r| r.pattern.clone()).collect();
+
+        Ok(Layer {
+            ignore_specs: SpecSet::from_lists(&ignore_lists)?,
+            skip_specs: SpecSet::from_lists(&skip_lists)?,
+            rules: rule_set,
+            rule_actions,
+        })
+    }
+
+    /// Evaluates the candidate on top of the result of the layers below. Without any matching
+    /// spec, that result is kept.
+    fn evaluate(&self, lower: MatchResult, candidate: &Candidate) -> MatchResult {
+        let ignore_match = self.ignore_specs.is_match(candidate);
+        let skip_match =
+            (ignore_match || lower != MatchResult::NoMatch) && self.skip_specs.is_match(candidate);
+
+        let result = match (ignore_match, skip_match) {
+            (_, true) => MatchResult::Skip,
+            (true, false) => MatchResult::Ignore,
+            (false, false) => lower,
         };
 
-        Ok(matcher)
+        if self.rules.is_empty() {
+            return result;
+        }
+
+        let rule_indices = self.rules.matches(candidate);
+        self.apply_rules(result, &rule_indices)
+    }
+
+    /// Adds the matching specs to the report and evaluates the result on top of it. Returns
+    /// whether any spec matched.
+    fn report<'a>(&'a self, candidate: &Candidate, report: &mut MatchReport<'a>) -> bool {
+        let lower = std::mem::replace(&mut report.result, MatchResult::NoMatch);
+
+        let ignore_specs = self.ignore_specs.refs(self.ignore_specs.matches(candidate));
+        let skip_specs = if ignore_specs.is_empty() && lower == MatchResult::NoMatch {
+            vec![]
+        } else {
+            self.skip_specs.refs(self.skip_specs.matches(candidate))
+        };
+        let rule_indices = self.rules.matches(candidate);
+        let matched =
+            !ignore_specs.is_empty() || !skip_specs.is_empty() || !rule_indices.is_empty();
+
+        report.result = self.evaluate(lower, candidate);
+        report.ignore_specs.extend(ignore_specs);
+        report.skip_specs.extend(skip_specs);
+        for index in &rule_indices {
+            match self.rule_actions[*index] {
+                RuleAction::Ignore => report.ignore_specs.push(self.rules.spec(*index)),
+                RuleAction::Skip => report.skip_specs.push(self.rules.spec(*index)),
+            }
+        }
+        if matched {
+            report.rule = rule_indices.last().map(|i| self.rules.spec(*i));
+        }
+
+        matched
+    }
+
+    /// Returns `None` without any matching spec, otherwise the spec keeping the candidate from
+    /// being ignored, if any.
+    fn skip_decision(&self, candidate: &Candidate) -> Option<Option<SpecRef<'_>>> {
+        if let Some(last) = self.rules.matches(candidate).last() {
+            return Some(match self.rule_actions[*last] {
+                RuleAction::Skip => Some(self.rules.spec(*last)),
+                RuleAction::Ignore => None,
+            });
+        }
+
+        if let Some(index) = self.skip_specs.matches(candidate).into_iter().min() {
+            return Some(Some(self.skip_specs.spec(index)));
+        }
+
+        if self.ignore_specs.is_match(candidate) {
+            return Some(None);
+        }
+
+        None
     }
 
-    pub fn matches(&self, path: String) -> bool {
-        let ignore_match = self.ignore_globset.is_match(&path);
+    /// Lets the last matching rule decide, skipping only turns a previous ignore into `Skip`.
+    fn apply_rules(&self, result: MatchResult, rule_indices: &[usize]) -> MatchResult {
+        let last = match rule_indices.last() {
+            Some(last) => *last,
+            None => return result,
+        };
+
+        let ignored_before = result != MatchResult::NoMatch
+            || rule_indices
+                .iter()
+                .any(|i| self.rule_actions[*i] == RuleAction::Ignore);
 
-        if !ignore_match {
-            return false;
+        match self.rule_actions[last] {
+            RuleAction::Ignore => MatchResult::Ignore,
+            RuleAction::Skip if ignored_before => MatchResult::Skip,
+            RuleAction::Skip => MatchResult::NoMatch,
         }
+    }
 
-        let skip_match = self.skip_globset.is_match(&path);
+    fn spec_sets(&self) -> [&SpecSet; 3] {
+        [&self.ignore_specs, &self.skip_specs, &self.rules]
+    }
+}
+
+/// The specs grouped by the form of the candidate they are matched against.
+struct SpecSet {
+    // the name of the list in the configuration and the index there by spec index, e.g.
+    // `presets[rust].ignore-specs` for specs of a preset following the configured ones
+    origins: Vec<(String, usize)>,
+    // the configured patterns by spec index
+    patterns: Vec<String>,
+    // patterns containing a `/` somewhere in between, e.g. `**/build`
+    absolute: (GlobSet, Vec<usize>),
+    // patterns with a leading `/`, e.g. `/Projects/*/build`, also matched against the absolute
+    // path as they used to be
+    anchored: (GlobSet, Vec<usize>),
+    // patterns without any `/`, e.g. `target`
+    file_name: (GlobSet, Vec<usize>),
+    // regular expressions matched against the name of the entry, e.g. `regex:^out\d+$`
+    regex_file_name: RegexSpecs,
+    // regular expressions matched against the path relative to the root, e.g.
+    // `path-regex:^/generated/`
+    regex_relative: RegexSpecs,
+    // specs without a pattern, matching any entry below the scanned root
+    any: Vec<usize>,
+    // by spec index
+    options: Vec<SpecOptions>,
+}
+
+/// The regular expressions of one form, where the ones using look-around or backreferences are
+/// matched one by one, as only the `regex` crate supports matching many at once.
+struct RegexSpecs {
+    set: (RegexSet, Vec<usize>),
+    // by spec index
+    fancy: Vec<(fancy_regex::Regex, usize)>,
+}
+
+/// The restrictions of a spec besides its pattern.
+struct SpecOptions {
+    entry_type: EntryType,
+    min_depth: Option<usize>,
+    max_depth: Option<usize>,
+    when_sibling: Vec<String>,
+    when_parent_contains: Vec<String>,
+    when_contains: Vec<String>,
+    min_size: Option<u64>,
+    min_entries: Option<u64>,
+    older_than: Option<Duration>,
+    newer_than: Option<Duration>,
+}
 
-        !skip_match
+impl From<&MatcherSpec> for SpecOptions {
+    fn from(ms: &MatcherSpec) -> Self {
+        SpecOptions {
+            entry_type: ms.entry_type,
+            min_depth: ms.min_depth,
+            max_depth: ms.max_depth,
+            when_sibling: ms.when_sibling.clone(),
+            when_parent_contains: ms.when_parent_contains.clone(),
+            when_contains: ms.when_contains.clone(),
+            min_size: ms.min_size,
+            min_entries: ms.min_entries,
+            older_than: ms.older_than,
+            newer_than: ms.newer_than,
+        }
     }
 }
 
-fn build_globset(builder: &mut GlobSetBuilder, matcher_specs: &[MatcherSpec]) -> Result<(), Error> {
-    for matcher_spec in matcher_specs.iter() {
-        let glob = Glob::new(&matcher_spec.pattern);
+impl SpecOptions {
+    fn is_restricted(&self) -> bool {
+        self.entry_type != EntryType::Any
+            || self.min_depth.is_some()
+            || self.max_depth.is_some()
+            || self.markers().next().is_some()
+            || self.has_metric_predicates()
+    }
+
+    fn has_metric_predicates(&self) -> bool {
+        self.min_size.is_some()
+            || self.min_entries.is_some()
+            || self.older_than.is_some()
+            || self.newer_than.is_some()
+    }
+
+    fn markers(&self) -> impl Iterator<Item = &String> {
+        self.when_sibling
+            .iter()
+            .chain(&self.when_parent_contains)
+            .chain(&self.when_contains)
+    }
+
+    fn accepts(&self, candidate: &Candidate) -> bool {
+        let entry_type_accepted = match self.entry_type {
+            EntryType::Any => true,
+            entry_type => candidate.entry_type == Some(entry_type),
+        };
+
+        entry_type_accepted
+            && self.min_depth.map(|d| candidate.depth >= d).unwrap_or(true)
+            && self.max_depth.map(|d| candidate.depth <= d).unwrap_or(true)
+            && self.conditions_met(candidate)
+            && self.metric_predicates_met(candidate)
+    }
 
-        if glob.is_err() {
-            return Err(glob.err().unwrap());
+    /// Measures the entry if needed, which is the most expensive check, so it comes last.
+    fn metric_predicates_met(&self, candidate: &Candidate) -> bool {
+        if !self.has_metric_predicates() {
+            return true;
         }
 
-        builder.add(glob.unwrap());
+        let metrics = match candidate.metrics.map(|m| m.get(candidate.path)) {
+            Some(Ok(metrics)) => metrics,
+            Some(Err(e)) => {
+                warn!("{}", e);
+                return false;
+            }
+            None => return false,
+        };
+        let age = SystemTime::now()
+            .duration_since(metrics.modified)
+            .unwrap_or_default();
+
+        self.min_size.map(|s| metrics.size >= s).unwrap_or(true)
+            && self
+                .min_entries
+                .map(|e| metrics.entries >= e)
+                .unwrap_or(true)
+            && self.older_than.map(|d| age >= d).unwrap_or(true)
+            && self.newer_than.map(|d| age <= d).unwrap_or(true)
+    }
+
+    /// Checks the marker conditions against the filesystem, so only do it for matching specs.
+    fn conditions_met(&self, candidate: &Candidate) -> bool {
+        let path = candidate.path;
+
+        (self.when_sibling.is_empty()
+            || path
+                .parent()
+                .map(|p| contains_any(p, &self.when_sibling))
+                .unwrap_or(false))
+            && (self.when_parent_contains.is_empty()
+                || path
+                    .ancestors()
+                    .skip(1)
+                    .take(candidate.depth)
+                    .any(|a| contains_any(a, &self.when_parent_contains)))
+            && (self.when_contains.is_empty() || contains_any(path, &self.when_contains))
     }
-    builder.build()?;
+}
+
+/// Rejects what only rules support, as it would otherwise be ignored or taken literally.
+fn reject_rule_options(lists: &[(String, &[MatcherSpec])]) -> Result<(), Error> {
+    for (specs, matcher_specs) in lists {
+        for (index, matcher_spec) in matcher_specs.iter().enumerate() {
+            let reason = if matcher_spec.action != RuleAction::Ignore {
+                "`action` is only supported by rules"
+            } else if matcher_spec.regex().is_none() && matcher_spec.pattern.starts_with('!') {
+                "a leading `!` only negates rules, use a skip-spec or a rule instead"
+            } else {
+                continue;
+            };
+
+            return Err(Error::Spec {
+                specs: specs.clone(),
+                index,
+                reason: String::from(reason),
+            });
+        }
+    }
+
     Ok(())
 }
 
+fn contains_any(directory: &Path, markers: &[String]) -> bool {
+    markers.iter().any(|m| directory.join(m).exists())
+}
+
+impl SpecSet {
+    fn new(matcher_specs: &[MatcherSpec], specs: &str) -> Result<Self, Error> {
+        Self::from_lists(&[(specs.to_string(), matcher_specs)])
+    }
+
+    /// Combines the lists into one set, while specs are still referred to by their own list.
+    fn from_lists(lists: &[(String, &[MatcherSpec])]) -> Result<Self, Error> {
+        let matcher_specs: Vec<&MatcherSpec> =
+            lists.iter().flat_map(|(_, specs)| specs.iter()).collect();
+        let origins: Vec<(String, usize)> = lists
+            .iter()
+            .flat_map(|(name, specs)| (0..specs.len()).map(move |i| (name.clone(), i)))
+            .collect();
+
+        let mut absolute = Vec::new();
+        let mut anchored = Vec::new();
+        let mut file_name = Vec::new();
+        let mut regex_file_name = Vec::new();
+        let mut regex_relative = Vec::new();
+        let mut any = Vec::new();
+
+        for (index, matcher_spec) in matcher_specs.iter().copied().enumerate() {
+            let pattern = matcher_spec.pattern.as_str();
+            if pattern.is_empty() {
+                if !matcher_spec.has_metric_predicates() {
+                    let (specs, index) = origins[index].clone();
+                    return Err(Error::Spec {
+                        specs,
+                        index,
+                        reason: String::from("a pattern is required without metric predicates"),
+                    });
+                }
+                any.push(index);
+            } else if matcher_spec.regex().is_some() {
+                if matcher_spec.is_path_regex() {
+                    regex_relative.push((index, matcher_spec));
+                } else {
+                    regex_file_name.push((index, matcher_spec));
+                }
+            } else if pattern.starts_with('/') {
+                anchored.push((index, matcher_spec));
+            } else if !pattern.contains('/') {
+                file_name.push((index, matcher_spec));
+            } else {
+                absolute.push((index, matcher_spec));
+            }
+        }
+
+        let options = matcher_specs
+            .iter()
+            .copied()
+            .map(SpecOptions::from)
+            .collect();
+
+        Ok(SpecSet {
+            patterns: matcher_specs.iter().map(|ms| ms.pattern.clone()).collect(),
+            absolute: build_globset(&absolute, &origins)?,
+            anchored: build_globset(&anchored, &origins)?,
+            file_name: build_globset(&file_name, &origins)?,
+            regex_file_name: RegexSpecs::new(&regex_file_name, &origins)?,
+            regex_relative: RegexSpecs::new(&regex_relative, &origins)?,
+            any,
+            options,
+            origins,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    fn spec(&self, index: usize) -> SpecRef<'_> {
+        let (specs, list_index) = &self.origins[index];
+
+        SpecRef {
+            specs,
+            index: *list_index,
+            pattern: &self.patterns[index],
+        }
+    }
+
+    fn refs(&self, indices: Vec<usize>) -> Vec<SpecRef<'_>> {
+        indices.into_iter().map(|i| self.spec(i)).collect()
+    }
+
+    fn needs_entry_type(&self) -> bool {
+        self.options.iter().any(|o| o.entry_type != EntryType::Any)
+    }
+
+    fn needs_metrics(&self) -> bool {
+        self.options.iter().any(SpecOptions::has_metric_predicates)
+    }
+
+    fn is_marker(&self, file_name: &str) -> bool {
+        self.options
+            .iter()
+            .any(|o| o.markers().any(|m| m == file_name))
+    }
+
+    fn is_match(&self, candidate: &Candidate) -> bool {
+        if self.options.iter().any(SpecOptions::is_restricted) {
+            return !self.matches(candidate).is_empty();
+        }
+
+        self.absolute.0.is_match(&candidate.matched_path)
+            || self.anchored.0.is_match(&candidate.relative_path)
+            || self.anchored.0.is_match(&candidate.matched_path)
+            || self.file_name.0.is_match(&candidate.file_name)
+            || self
+                .regex_file_name
+                .is_match(&candidate.file_name.to_string_lossy())
+            || self.regex_relative.is_match(&candidate.relative_path)
+    }
+
+    /// Returns the indices of all matching specs.
+    fn matches(&self, candidate: &Candidate) -> Vec<usize> {
+        let forms = [
+            (&self.absolute, candidate.matched_path.as_ref()),
+            (&self.anchored, Path::new(&candidate.relative_path)),
+            (&self.file_name, candidate.file_name.as_ref()),
+        ];
+
+        let mut indices: Vec<usize> = forms
+            .iter()
+            .flat_map(|((globset, indices), form)| {
+                globset.matches(form).into_iter().map(move |i| indices[i])
+            })
+            .chain(
+                self.anchored
+                    .0
+                    .matches(&candidate.matched_path)
+                    .into_iter()
+                    .map(|i| self.anchored.1[i]),
+            )
+            .chain(
+                self.regex_file_name
+                    .matches(&candidate.file_name.to_string_lossy()),
+            )
+            .chain(self.regex_relative.matches(&candidate.relative_path))
+            .chain(self.any.iter().copied().filter(|_| candidate.depth > 0))
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.retain(|i| self.options[*i].accepts(candidate));
+
+        indices
+    }
+}
+
+/// Returns the path a scope applies below as matched against, e.g. `/Clients` for `Clients/`,
+/// empty for the root.
+pub fn scope_path(path: &str) -> String {
+    let path = path.trim_matches('/');
+
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+/// Whether a spec restricts the entries it matches by anything besides its pattern.
+pub fn is_restricted(matcher_spec: &MatcherSpec) -> bool {
+    SpecOptions::from(matcher_spec).is_restricted()
+}
+
+/// Brings the patterns and scope paths of the configuration into the given normal form.
+fn normalized_config(matcher_config: &MatcherConfig, form: Normalization) -> MatcherConfig {
+    let mut normalized = matcher_config.clone();
+
+    let scopes = normalized.scopes.iter_mut().flatten();
+    let mut spec_lists = vec![
+        &mut normalized.ignore_specs,
+        &mut normalized.skip_specs,
+        &mut normalized.rules,
+    ];
+    for preset in normalized.presets.iter_mut() {
+        spec_lists.extend([&mut preset.ignore_specs, &mut preset.skip_specs]);
+    }
+    for scope in scopes {
+        scope.path = normalize(form, &scope.path).into_owned();
+        spec_lists.extend([
+            &mut scope.ignore_specs,
+            &mut scope.skip_specs,
+            &mut scope.rules,
+        ]);
+    }
+
+    for spec in spec_lists.into_iter().flatten().flatten() {
+        spec.pattern = normalize(form, &spec.pattern).into_owned();
+    }
+
+    normalized
+}
+
+fn normalize(form: Normalization, text: &str) -> Cow<'_, str> {
+    match form {
+        Normalization::Nfc if is_nfc(text) => Cow::Borrowed(text),
+        Normalization::Nfd if is_nfd(text) => Cow::Borrowed(text),
+        Normalization::Nfc => Cow::Owned(text.nfc().collect()),
+        Normalization::Nfd => Cow::Owned(text.nfd().collect()),
+    }
+}
+
+fn build_globset(
+    matcher_specs: &[(usize, &MatcherSpec)],
+    origins: &[(String, usize)],
+) -> Result<(GlobSet, Vec<usize>), Error> {
+    let mut builder = GlobSetBuilder::new();
+
+    for (index, matcher_spec) in matcher_specs {
+        let glob = GlobBuilder::new(&matcher_spec.pattern)
+            .case_insensitive(matcher_spec.case_insensitive)
+            .build()
+            .map_err(|source| Error::Pattern {
+                specs: origins[*index].0.clone(),
+                index: origins[*index].1,
+                source,
+            })?;
+
+        builder.add(glob);
+    }
+
+    let globset = builder.build().map_err(|source| {
+        let index = matcher_specs
+            .iter()
+            .find(|(_, ms)| Some(ms.pattern.as_str()) == source.glob())
+            .map(|(index, _)| *index)
+            .unwrap_or_default();
+
+        Error::Pattern {
+            specs: origins[index].0.clone(),
+            index: origins[index].1,
+            source,
+        }
+    })?;
+
+    Ok((
+        globset,
+        matcher_specs.iter().map(|(index, _)| *index).collect(),
+    ))
+}
+
+impl RegexSpecs {
+    fn new(
+        matcher_specs: &[(usize, &MatcherSpec)],
+        origins: &[(String, usize)],
+    ) -> Result<Self, Error> {
+        let mut regexes = Vec::new();
+        let mut indices = Vec::new();
+        let mut fancy = Vec::new();
+
+        // compile each one on its own first, to know which one is broken or needs backtracking
+        for (index, matcher_spec) in matcher_specs {
+            let regex = matcher_spec.regex().unwrap_or_default();
+            let regex = if matcher_spec.case_insensitive {
+                format!("(?i:{})", regex)
+            } else {
+                regex.to_string()
+            };
+
+            if RegexBuilder::new(&regex).build().is_ok() {
+                regexes.push(regex);
+                indices.push(*index);
+                continue;
+            }
+
+            let fancy_regex = fancy_regex::Regex::new(&regex).map_err(|source| Error::Regex {
+                specs: origins[*index].0.clone(),
+                index: origins[*index].1,
+                source,
+            })?;
+            fancy.push((fancy_regex, *index));
+        }
+
+        let regexset = RegexSetBuilder::new(&regexes).build().map_err(|source| {
+            let index = indices.first().copied().unwrap_or_default();
+            Error::Regex {
+                specs: origins[index].0.clone(),
+                index: origins[index].1,
+                source: fancy_regex::Error::InnerError(source),
+            }
+        })?;
+
+        Ok(RegexSpecs {
+            set: (regexset, indices),
+            fancy,
+        })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        self.set.0.is_match(text) || self.fancy.iter().any(|(r, _)| fancy_match(r, text))
+    }
+
+    /// Returns the indices of all matching specs.
+    fn matches<'a>(&'a self, text: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.set
+            .0
+            .matches(text)
+            .into_iter()
+            .map(move |i| self.set.1[i])
+            .chain(
+                self.fancy
+                    .iter()
+                    .filter(move |(r, _)| fancy_match(r, text))
+                    .map(|(_, index)| *index),
+            )
+    }
+}
+
+/// Backtracking may exceed its limit, which doesn't count as a match then.
+fn fancy_match(regex: &fancy_regex::Regex, text: &str) -> bool {
+    regex.is_match(text).unwrap_or_else(|e| {
+        warn!(
+            "Failed to match {:?} against {:?}: {}",
+            regex.as_str(),
+            text,
+            e
+        );
+        false
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::configuration::{Normalization, PatternKind, PresetConfig, ScopeConfig};
     use rstest::rstest;
+    use std::fs;
+    use tempfile::TempDir;
 
     #[rstest(
         path,
@@ -80,14 +1017,14 @@ mod tests {
         case::not_matching_specific("/foo/src/build", false)
     )]
     fn no_matches_with_empty_config(path: &str, expected: bool) {
-        let matcher_config = MatcherConfig {
-            ignore_specs: None,
-            skip_specs: None,
-        };
+        let matcher_config = MatcherConfig::default();
 
         let matcher = Matcher::new(&Some(matcher_config));
 
-        assert_eq!(expected, matcher.unwrap().matches(path.to_string()));
+        assert_eq!(
+            expected,
+            matcher.unwrap().evaluate(&Candidate::new("/", path)) == MatchResult::Ignore
+        );
     }
 
     #[rstest(
@@ -106,44 +1043,1101 @@ mod tests {
             ignore_specs: Some(vec![
                 MatcherSpec {
                     pattern: String::from("**/build"),
+                    ..Default::default()
                 },
                 MatcherSpec {
                     pattern: String::from("**/target"),
+                    ..Default::default()
                 },
             ]),
             skip_specs: Some(vec![MatcherSpec {
                 pattern: String::from("**/src/build"),
+                ..Default::default()
             }]),
+            ..Default::default()
         };
 
         let matcher = Matcher::new(&Some(matcher_config));
 
-        assert_eq!(expected, matcher.unwrap().matches(path.to_string()));
+        assert_eq!(
+            expected,
+            matcher.unwrap().evaluate(&Candidate::new("/", path)) == MatchResult::Ignore
+        );
     }
 
     #[rstest(
-        matcher_config,
-        case::ignore_specs(MatcherConfig {
-            ignore_specs: Some(vec![
-                MatcherSpec {
-                    pattern: String::from("**/src/bu{ild"),
-                }
-            ]),
-            skip_specs: None,
-        }),
-        case::skip_specs(MatcherConfig {
-            ignore_specs: None,
+        path,
+        expected,
+        case::ignore("/foo/build", MatchResult::Ignore),
+        case::skip("/foo/src/build", MatchResult::Skip),
+        case::no_match("/foo/bar", MatchResult::NoMatch)
+    )]
+    fn evaluates_with_config(path: &str, expected: MatchResult) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("**/build"),
+                ..Default::default()
+            }]),
+            skip_specs: Some(vec![MatcherSpec {
+                pattern: String::from("**/src/build"),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config));
+
+        assert_eq!(
+            expected,
+            matcher.unwrap().evaluate(&Candidate::new("/", path))
+        );
+    }
+
+    #[rstest(
+        path,
+        expected,
+        case::first("/foo/src/build", Some((0, "**/src/build"))),
+        case::second("/foo/keep/target", Some((1, "**/keep/*"))),
+        case::none("/foo/build", None)
+    )]
+    fn skip_spec_with_config(path: &str, expected: Option<(usize, &str)>) {
+        let matcher_config = MatcherConfig {
             skip_specs: Some(vec![
                 MatcherSpec {
-                    pattern: String::from("**/src/bu{ild"),
-                }
+                    pattern: String::from("**/src/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("**/keep/*"),
+                    ..Default::default()
+                },
             ]),
-        }),
-    )]
-    fn broken_configuration(matcher_config: MatcherConfig) {
-        let matcher = Matcher::new(&Some(matcher_config));
+            ..Default::default()
+        };
 
-        assert!(matcher.is_err());
-        assert!(matcher.err().unwrap().contains("error parsing glob"))
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+
+        assert_eq!(
+            expected,
+            matcher
+                .skip_spec(&Candidate::new("/", path))
+                .map(|s| (s.index, s.pattern))
+        );
+    }
+
+    #[rstest(
+        path,
+        expected,
+        case::anchored("/home/charly/Dropbox/Projects/foo/build", MatchResult::Ignore),
+        case::anchored_deeper(
+            "/home/charly/Dropbox/Archive/Projects/foo/build",
+            MatchResult::NoMatch
+        ),
+        case::anchored_absolute("/home/charly/Dropbox/old/stuff", MatchResult::Ignore),
+        case::file_name("/home/charly/Dropbox/foo/bar/target", MatchResult::Ignore),
+        case::file_name_skipped("/home/charly/Dropbox/vendor/target", MatchResult::Skip),
+        case::file_name_glob("/home/charly/Dropbox/foo/debug.log", MatchResult::Ignore),
+        case::file_name_partial("/home/charly/Dropbox/foo/target2", MatchResult::NoMatch)
+    )]
+    fn evaluates_pattern_forms(path: &str, expected: MatchResult) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("/Projects/*/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("/home/charly/Dropbox/old/**"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("target"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("*.log"),
+                    ..Default::default()
+                },
+            ]),
+            skip_specs: Some(vec![MatcherSpec {
+                pattern: String::from("/vendor/**"),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+
+        assert_eq!(
+            expected,
+            matcher.evaluate(&Candidate::new("/home/charly/Dropbox", path))
+        );
+    }
+
+    #[rstest(
+        path,
+        expected_result,
+        expected_ignore_specs,
+        expected_skip_specs,
+        case::ignored("/foo/build", MatchResult::Ignore, vec![(0, "**/build")], vec![]),
+        case::multiple("/foo/target/build", MatchResult::Ignore, vec![(0, "**/build"), (2, "**/target/**")], vec![]),
+        case::skipped("/foo/src/build", MatchResult::Skip, vec![(0, "**/build")], vec![(0, "**/src/build")]),
+        case::not_matching("/foo/src", MatchResult::NoMatch, vec![], vec![])
+    )]
+    fn reports_matching_specs(
+        path: &str,
+        expected_result: MatchResult,
+        expected_ignore_specs: Vec<(usize, &str)>,
+        expected_skip_specs: Vec<(usize, &str)>,
+    ) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("**/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("**/target"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("**/target/**"),
+                    ..Default::default()
+                },
+            ]),
+            skip_specs: Some(vec![MatcherSpec {
+                pattern: String::from("**/src/build"),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let candidate = Candidate::new("/", path);
+        let report = matcher.report(&candidate);
+
+        assert_eq!(expected_result, report.result);
+        assert_eq!(expected_result, matcher.evaluate(&candidate));
+        assert_eq!(
+            expected_ignore_specs,
+            index_and_pattern(&report.ignore_specs)
+        );
+        assert_eq!(expected_skip_specs, index_and_pattern(&report.skip_specs));
+    }
+
+    #[rstest(
+        root,
+        path,
+        expected_relative_path,
+        expected_file_name,
+        case::below_root("/foo", "/foo/bar/baz", "/bar/baz", "baz"),
+        case::root("/foo", "/foo", "/", "foo"),
+        case::filesystem_root("/", "/bar", "/bar", "bar"),
+        case::outside_of_root("/foo", "/foobar", "/foobar", "foobar")
+    )]
+    fn candidate_forms(
+        root: &str,
+        path: &str,
+        expected_relative_path: &str,
+        expected_file_name: &str,
+    ) {
+        let candidate = Candidate::new(root, path);
+
+        assert_eq!(Path::new(path), candidate.path);
+        assert_eq!(expected_relative_path, candidate.relative_path);
+        assert_eq!(Path::new(expected_file_name), candidate.file_name);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn non_utf8_candidate_forms() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/foo/caf\xe9/build"));
+        let candidate = Candidate::new("/foo", path);
+
+        assert_eq!(path, candidate.path);
+        assert_eq!("/caf\u{fffd}/build", candidate.relative_path);
+        assert_eq!(Path::new("build"), candidate.file_name);
+        assert_eq!(2, candidate.depth);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[rstest(
+        pattern,
+        path,
+        expected,
+        case::file_name("build", b"/foo/caf\xe9/build", MatchResult::Ignore),
+        case::non_utf8_file_name("caf*", b"/foo/caf\xe9", MatchResult::Ignore),
+        case::non_utf8_absolute("/foo/caf?", b"/foo/caf\xe9", MatchResult::Ignore),
+        case::anchored("/foo/*/build", b"/foo/caf\xe9/build", MatchResult::Ignore),
+        case::lossy_anchored("/foo/caf\u{fffd}", b"/foo/caf\xe9", MatchResult::Ignore),
+        case::lossy_regex("regex:^caf.$", b"/foo/caf\xe9", MatchResult::Ignore),
+        case::other_name("cafe", b"/foo/caf\xe9", MatchResult::NoMatch)
+    )]
+    fn evaluates_non_utf8_names(pattern: &str, path: &[u8], expected: MatchResult) {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from(pattern),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let path = Path::new(OsStr::from_bytes(path));
+
+        assert_eq!(expected, matcher.evaluate(&Candidate::new("/", path)));
+    }
+
+    #[rstest(
+        normalization,
+        pattern,
+        path,
+        expected,
+        case::nfc_decomposed_path(
+            Some(Normalization::Nfc),
+            "**/Entw\u{fc}rfe/export",
+            "/Entwu\u{308}rfe/export",
+            MatchResult::Ignore
+        ),
+        case::nfc_decomposed_pattern(
+            Some(Normalization::Nfc),
+            "**/Entwu\u{308}rfe/export",
+            "/Entw\u{fc}rfe/export",
+            MatchResult::Ignore
+        ),
+        case::nfd_decomposed_path(
+            Some(Normalization::Nfd),
+            "**/Entw\u{fc}rfe/export",
+            "/Entwu\u{308}rfe/export",
+            MatchResult::Ignore
+        ),
+        case::nfd_composed_path(
+            Some(Normalization::Nfd),
+            "**/Entwu\u{308}rfe/export",
+            "/Entw\u{fc}rfe/export",
+            MatchResult::Ignore
+        ),
+        case::anchored(
+            Some(Normalization::Nfc),
+            "/Entw\u{fc}rfe",
+            "/Entwu\u{308}rfe",
+            MatchResult::Ignore
+        ),
+        case::file_name(
+            Some(Normalization::Nfd),
+            "Entw\u{fc}rfe",
+            "/Entwu\u{308}rfe",
+            MatchResult::Ignore
+        ),
+        case::regex(
+            Some(Normalization::Nfc),
+            "regex:^Entw\u{fc}rfe$",
+            "/Entwu\u{308}rfe",
+            MatchResult::Ignore
+        ),
+        case::without_normalization(
+            None,
+            "**/Entw\u{fc}rfe/export",
+            "/Entwu\u{308}rfe/export",
+            MatchResult::NoMatch
+        )
+    )]
+    fn evaluates_normalized_names(
+        normalization: Option<Normalization>,
+        pattern: &str,
+        path: &str,
+        expected: MatchResult,
+    ) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from(pattern),
+                ..Default::default()
+            }]),
+            unicode_normalization: normalization,
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let path = format!("/home/charly/Dropbox{}", path);
+
+        assert_eq!(
+            expected,
+            matcher.evaluate(&Candidate::new("/home/charly/Dropbox", &path))
+        );
+    }
+
+    #[test]
+    fn normalizes_scopes() {
+        let matcher_config = MatcherConfig {
+            scopes: Some(vec![ScopeConfig {
+                path: String::from("Entw\u{fc}rfe"),
+                ignore_specs: Some(vec![MatcherSpec {
+                    pattern: String::from("/export"),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }]),
+            unicode_normalization: Some(Normalization::Nfc),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let candidate = Candidate::new("/", "/Entwu\u{308}rfe/export");
+
+        assert_eq!(MatchResult::Ignore, matcher.evaluate(&candidate));
+        assert_eq!(
+            Some("scopes[0] \"Entw\u{fc}rfe\""),
+            matcher.report(&candidate).scope
+        );
+    }
+
+    #[rstest(
+        path,
+        entry_type,
+        expected,
+        case::dir("/foo/build", Some(EntryType::Dir), MatchResult::Ignore),
+        case::file("/foo/build", Some(EntryType::File), MatchResult::NoMatch),
+        case::unknown_type("/foo/build", None, MatchResult::NoMatch),
+        case::case_insensitive("/foo/Debug.LOG", Some(EntryType::File), MatchResult::Ignore),
+        case::case_sensitive("/foo/Build", Some(EntryType::Dir), MatchResult::NoMatch),
+        case::below_min_depth("/node_modules", Some(EntryType::Dir), MatchResult::NoMatch),
+        case::within_depth("/foo/node_modules", Some(EntryType::Dir), MatchResult::Ignore),
+        case::above_max_depth("/foo/bar/node_modules", None, MatchResult::NoMatch),
+        case::skip_spec_within_depth("/foo/bar/build", Some(EntryType::Dir), MatchResult::Skip)
+    )]
+    fn evaluates_spec_options(path: &str, entry_type: Option<EntryType>, expected: MatchResult) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("build"),
+                    entry_type: EntryType::Dir,
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("*.log"),
+                    case_insensitive: true,
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("node_modules"),
+                    min_depth: Some(2),
+                    max_depth: Some(2),
+                    ..Default::default()
+                },
+            ]),
+            skip_specs: Some(vec![MatcherSpec {
+                pattern: String::from("build"),
+                min_depth: Some(3),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let mut candidate = Candidate::new("/", path);
+        if let Some(entry_type) = entry_type {
+            candidate = candidate.with_entry_type(entry_type);
+        }
+
+        assert!(matcher.needs_entry_type());
+        assert_eq!(expected, matcher.evaluate(&candidate));
+    }
+
+    #[rstest(
+        path,
+        expected,
+        case::sibling("rust/target", MatchResult::Ignore),
+        case::other_sibling("java/target", MatchResult::Ignore),
+        case::without_sibling("design/target", MatchResult::NoMatch),
+        case::parent_contains("web/src/dist", MatchResult::Ignore),
+        case::without_parent_contains("design/dist", MatchResult::NoMatch),
+        case::contains("cache", MatchResult::Ignore),
+        case::without_contains("design/cache", MatchResult::NoMatch)
+    )]
+    fn evaluates_marker_conditions(path: &str, expected: MatchResult) {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        for marker in &[
+            "rust/Cargo.toml",
+            "java/pom.xml",
+            "web/package.json",
+            "cache/CACHEDIR.TAG",
+        ] {
+            fs::create_dir_all(root.join(marker).parent().unwrap()).unwrap();
+            fs::write(root.join(marker), "").unwrap();
+        }
+        for directory in &[
+            "design/target",
+            "design/dist",
+            "design/cache",
+            "web/src/dist",
+        ] {
+            fs::create_dir_all(root.join(directory)).unwrap();
+        }
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("target"),
+                    when_sibling: vec![String::from("Cargo.toml"), String::from("pom.xml")],
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("dist"),
+                    when_parent_contains: vec![String::from("package.json")],
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("cache"),
+                    when_contains: vec![String::from("CACHEDIR.TAG")],
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let path = root.join(path);
+
+        assert!(matcher.is_marker(&root.join("web/package.json")));
+        assert!(!matcher.is_marker(&root.join("web/index.html")));
+        assert_eq!(expected, matcher.evaluate(&Candidate::new(&root, &path)));
+    }
+
+    #[rstest(
+        matcher_config,
+        expected,
+        case::ignore_specs(MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("**/src/bu{ild"),
+                    ..Default::default()
+                }
+            ]),
+            ..Default::default()
+        }, "ignore-specs[0]"),
+        case::skip_specs(MatcherConfig {
+            skip_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("**/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("**/src/bu{ild"),
+                    ..Default::default()
+                }
+            ]),
+            ..Default::default()
+        }, "skip-specs[1]"),
+    )]
+    fn broken_configuration(matcher_config: MatcherConfig, expected: &str) {
+        let matcher = Matcher::new(&Some(matcher_config));
+
+        assert!(matcher.is_err());
+        let message = matcher.err().unwrap().to_string();
+        assert!(message.contains("error parsing glob"));
+        assert!(message.contains(expected));
+    }
+
+    #[rstest(
+        matcher_config,
+        expected,
+        case::ignore_specs(MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("**/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("regex:^out(\\d+$"),
+                    ..Default::default()
+                }
+            ]),
+            ..Default::default()
+        }, "ignore-specs[1]"),
+        case::skip_specs(MatcherConfig {
+            skip_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("/src/[b-a]"),
+                    kind: PatternKind::Regex,
+                    ..Default::default()
+                }
+            ]),
+            ..Default::default()
+        }, "skip-specs[0]"),
+        case::look_around(MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("regex:^(?!keep.*\\.tmp$"),
+                    ..Default::default()
+                }
+            ]),
+            ..Default::default()
+        }, "parenthesis"),
+    )]
+    fn broken_regex_configuration(matcher_config: MatcherConfig, expected: &str) {
+        let matcher = Matcher::new(&Some(matcher_config));
+
+        assert!(matcher.is_err());
+        let message = matcher.err().unwrap().to_string();
+        assert!(message.contains("Invalid regular expression"));
+        assert!(message.contains(expected));
+    }
+
+    #[rstest(
+        path,
+        expected,
+        case::file_name("/foo/out42", MatchResult::Ignore),
+        case::file_name_without_digits("/foo/output", MatchResult::NoMatch),
+        case::case_insensitive("/foo/TMP-1", MatchResult::Ignore),
+        case::relative_path("/generated/v2/api", MatchResult::Ignore),
+        case::relative_path_elsewhere("/foo/generated/v2/api", MatchResult::NoMatch),
+        case::file_name_with_slash("/foo/dist-old", MatchResult::Ignore),
+        case::skipped("/vendor/out1", MatchResult::Skip),
+        case::glob("/foo/target", MatchResult::Ignore),
+        case::look_around("/foo/cache.TMP", MatchResult::Ignore),
+        case::negative_look_around("/foo/keep.tmp", MatchResult::NoMatch),
+        case::backreference("/mirror/a/a/docs", MatchResult::Ignore),
+        case::backreference_differing("/mirror/a/b/docs", MatchResult::NoMatch)
+    )]
+    fn evaluates_regex_patterns(path: &str, expected: MatchResult) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("^out\\d+$"),
+                    kind: PatternKind::Regex,
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("regex:^tmp-"),
+                    case_insensitive: true,
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("path-regex:^/generated/v\\d+/"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("^dist[^/]*$"),
+                    kind: PatternKind::Regex,
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("target"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("regex:^(?!keep).*\\.tmp$"),
+                    case_insensitive: true,
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("^/mirror/(\\w+)/\\1/"),
+                    kind: PatternKind::PathRegex,
+                    ..Default::default()
+                },
+            ]),
+            skip_specs: Some(vec![MatcherSpec {
+                pattern: String::from("^/vendor/"),
+                kind: PatternKind::PathRegex,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+
+        assert_eq!(
+            expected,
+            matcher.evaluate(&Candidate::new(
+                "/home/charly/Dropbox",
+                &format!("/home/charly/Dropbox{}", path)
+            ))
+        );
+    }
+
+    #[rstest(
+        path,
+        expected,
+        case::ignored("/foo/build", MatchResult::Ignore),
+        case::negated("/foo/docs/build", MatchResult::Skip),
+        case::ignored_again("/foo/docs/build/tmp", MatchResult::Ignore),
+        case::skip_action("/vendor/build", MatchResult::Skip),
+        case::skip_without_ignore("/vendor/lib", MatchResult::NoMatch),
+        case::overriding_skip_spec("/foo/src/target", MatchResult::Ignore),
+        case::skip_spec("/foo/src/other", MatchResult::Skip),
+        case::not_matching("/foo/docs", MatchResult::NoMatch)
+    )]
+    fn evaluates_rules(path: &str, expected: MatchResult) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("/foo/src/*"),
+                ..Default::default()
+            }]),
+            skip_specs: Some(vec![MatcherSpec {
+                pattern: String::from("**/src/*"),
+                ..Default::default()
+            }]),
+            rules: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("**/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("!**/docs/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("**/docs/build/tmp"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("/vendor/**"),
+                    action: RuleAction::Skip,
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("target"),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let candidate = Candidate::new("/", path);
+
+        assert_eq!(expected, matcher.evaluate(&candidate));
+        assert_eq!(expected, matcher.report(&candidate).result);
+    }
+
+    #[test]
+    fn reports_deciding_rule() {
+        let matcher_config = MatcherConfig {
+            rules: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("**/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("!**/docs/build"),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let candidate = Candidate::new("/", "/docs/build");
+        let report = matcher.report(&candidate);
+        let skip_spec = matcher.skip_spec(&candidate).unwrap();
+
+        assert_eq!(MatchResult::Skip, report.result);
+        assert_eq!(
+            vec![(0, "**/build")],
+            index_and_pattern(&report.ignore_specs)
+        );
+        assert_eq!(
+            vec![(1, "!**/docs/build")],
+            index_and_pattern(&report.skip_specs)
+        );
+        assert_eq!(
+            "rules[1] \"!**/docs/build\"",
+            report.rule.unwrap().to_string()
+        );
+        assert_eq!("rules[1] \"!**/docs/build\"", skip_spec.to_string());
+    }
+
+    #[test]
+    fn broken_rules() {
+        let matcher_config = MatcherConfig {
+            rules: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("**/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("!**/src/bu{ild"),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config));
+
+        assert!(matcher.is_err());
+        let message = matcher.err().unwrap().to_string();
+        assert!(message.contains("error parsing glob"));
+        assert!(message.contains("rules[1]"));
+    }
+
+    #[rstest(
+        path,
+        expected,
+        expected_scope,
+        case::global("/Archive/build", MatchResult::Ignore, None),
+        case::client_renders(
+            "/Clients/acme/Renders",
+            MatchResult::Ignore,
+            Some("scopes[0] \"Clients\"")
+        ),
+        case::personal_renders("/Personal/Renders", MatchResult::NoMatch, None),
+        case::personal_build(
+            "/Personal/build",
+            MatchResult::Skip,
+            Some("scopes[1] \"/Personal/\"")
+        ),
+        case::anchored_to_scope(
+            "/Clients/out",
+            MatchResult::Ignore,
+            Some("scopes[0] \"Clients\"")
+        ),
+        case::inner_scope(
+            "/Clients/acme/build",
+            MatchResult::Skip,
+            Some("scopes[2] \"Clients/acme\"")
+        ),
+        case::not_below_scope("/ClientsOld/Renders", MatchResult::NoMatch, None)
+    )]
+    fn evaluates_scopes(path: &str, expected: MatchResult, expected_scope: Option<&str>) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("build"),
+                ..Default::default()
+            }]),
+            scopes: Some(vec![
+                ScopeConfig {
+                    path: String::from("Clients"),
+                    ignore_specs: Some(vec![
+                        MatcherSpec {
+                            pattern: String::from("**/Renders"),
+                            ..Default::default()
+                        },
+                        MatcherSpec {
+                            pattern: String::from("/out"),
+                            ..Default::default()
+                        },
+                    ]),
+                    ..Default::default()
+                },
+                ScopeConfig {
+                    path: String::from("/Personal/"),
+                    skip_specs: Some(vec![MatcherSpec {
+                        pattern: String::from("{build,Renders}"),
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                },
+                ScopeConfig {
+                    path: String::from("Clients/acme"),
+                    rules: Some(vec![MatcherSpec {
+                        pattern: String::from("!build"),
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let path = format!("/home/charly/Dropbox{}", path);
+        let candidate = Candidate::new("/home/charly/Dropbox", &path);
+        let report = matcher.report(&candidate);
+
+        assert_eq!(expected, matcher.evaluate(&candidate));
+        assert_eq!(expected, report.result);
+        assert_eq!(expected_scope, report.scope);
+        match expected {
+            MatchResult::Ignore => assert!(matcher.skip_spec(&candidate).is_none()),
+            MatchResult::Skip => assert!(matcher.skip_spec(&candidate).is_some()),
+            MatchResult::NoMatch => {}
+        }
+    }
+
+    #[rstest(
+        root,
+        scope_root,
+        expected,
+        case::scope_root(
+            "/home/charly/Dropbox/Clients",
+            Some("/home/charly/Dropbox"),
+            MatchResult::Ignore
+        ),
+        case::scanned_root("/home/charly/Dropbox/Clients", None, MatchResult::NoMatch),
+        case::outside_scope_root(
+            "/home/charly/Dropbox/Clients",
+            Some("/home/charly/Documents"),
+            MatchResult::NoMatch
+        ),
+        case::whole_scope_root(
+            "/home/charly/Dropbox",
+            Some("/home/charly/Dropbox"),
+            MatchResult::Ignore
+        )
+    )]
+    fn resolves_scopes_against_scope_root(
+        root: &str,
+        scope_root: Option<&str>,
+        expected: MatchResult,
+    ) {
+        let matcher_config = MatcherConfig {
+            scopes: Some(vec![ScopeConfig {
+                path: String::from("Clients"),
+                ignore_specs: Some(vec![MatcherSpec {
+                    pattern: String::from("/acme/out"),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let mut candidate = Candidate::new(root, "/home/charly/Dropbox/Clients/acme/out");
+        if let Some(scope_root) = scope_root {
+            candidate = candidate.with_scope_root(Path::new(scope_root));
+        }
+
+        assert_eq!(expected, matcher.evaluate(&candidate));
+    }
+
+    #[test]
+    fn broken_scope() {
+        let matcher_config = MatcherConfig {
+            scopes: Some(vec![ScopeConfig {
+                path: String::from("Clients"),
+                skip_specs: Some(vec![MatcherSpec {
+                    pattern: String::from("**/src/bu{ild"),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        let message = Matcher::new(&Some(matcher_config))
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert!(message.contains("scopes[0].skip-specs[0]"));
+    }
+
+    #[rstest(
+        path,
+        expected_specs,
+        case::configured("/a/build", vec![("ignore-specs", 0, "build")]),
+        case::preset("/a/target", vec![("presets[rust].ignore-specs", 0, "target")]),
+        case::both(
+            "/a/build/out",
+            vec![("ignore-specs", 1, "out"), ("presets[rust].ignore-specs", 1, "out")]
+        )
+    )]
+    fn reports_specs_of_presets(path: &str, expected_specs: Vec<(&str, usize, &str)>) {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("out"),
+                    ..Default::default()
+                },
+            ]),
+            presets: vec![PresetConfig {
+                name: String::from("rust"),
+                ignore_specs: Some(vec![
+                    MatcherSpec {
+                        pattern: String::from("target"),
+                        ..Default::default()
+                    },
+                    MatcherSpec {
+                        pattern: String::from("out"),
+                        ..Default::default()
+                    },
+                ]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let candidate = Candidate::new("/", path);
+        let report = matcher.report(&candidate);
+
+        assert_eq!(MatchResult::Ignore, report.result);
+        assert_eq!(
+            expected_specs,
+            report
+                .ignore_specs
+                .iter()
+                .map(|s| (s.specs, s.index, s.pattern))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn broken_preset() {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("build"),
+                ..Default::default()
+            }]),
+            presets: vec![PresetConfig {
+                name: String::from("rust"),
+                ignore_specs: Some(vec![MatcherSpec {
+                    pattern: String::from("tar{get"),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let message = Matcher::new(&Some(matcher_config))
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert!(message.contains("presets[rust].ignore-specs[0]"));
+    }
+
+    #[rstest(
+        matcher_spec,
+        expected,
+        case::action(
+            MatcherSpec {
+                pattern: String::from("vendor"),
+                action: RuleAction::Skip,
+                ..Default::default()
+            },
+            "`action` is only supported by rules"
+        ),
+        case::negation(
+            MatcherSpec {
+                pattern: String::from("!docs/build"),
+                ..Default::default()
+            },
+            "a leading `!` only negates rules"
+        )
+    )]
+    fn rule_options_outside_of_rules(matcher_spec: MatcherSpec, expected: &str) {
+        let matcher_config = MatcherConfig {
+            skip_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("docs"),
+                    ..Default::default()
+                },
+                matcher_spec,
+            ]),
+            ..Default::default()
+        };
+
+        let message = Matcher::new(&Some(matcher_config))
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert!(message.contains("skip-specs[1]"));
+        assert!(message.contains(expected));
+    }
+
+    #[test]
+    fn pattern_required_without_metric_predicates() {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    min_size: Some(1),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config));
+
+        assert!(matcher.is_err());
+        let message = matcher.err().unwrap().to_string();
+        assert!(message.contains("ignore-specs[1]"));
+        assert!(message.contains("pattern is required"));
+    }
+
+    #[rstest(
+        path,
+        with_metrics,
+        expected,
+        case::large_file("renders/large.mov", true, MatchResult::Ignore),
+        case::small_file("renders/small.mov", true, MatchResult::NoMatch),
+        case::many_entries("cache", true, MatchResult::Ignore),
+        case::few_entries("renders", true, MatchResult::NoMatch),
+        case::recent("fresh.log", true, MatchResult::NoMatch),
+        case::without_metrics("renders/large.mov", false, MatchResult::NoMatch),
+        case::root("", true, MatchResult::NoMatch)
+    )]
+    fn evaluates_metric_predicates(path: &str, with_metrics: bool, expected: MatchResult) {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("renders")).unwrap();
+        fs::write(root.join("renders/large.mov"), vec![0; 4096]).unwrap();
+        fs::write(root.join("renders/small.mov"), vec![0; 16]).unwrap();
+        fs::create_dir_all(root.join("cache/a/b/c")).unwrap();
+        fs::write(root.join("fresh.log"), "").unwrap();
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("*.mov"),
+                    min_size: Some(1024),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    min_entries: Some(3),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("*.log"),
+                    older_than: Some(Duration::from_secs(24 * 60 * 60)),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let matcher = Matcher::new(&Some(matcher_config)).unwrap();
+        let metrics = MetricsCache::default();
+        let path = root.join(path);
+        let mut candidate = Candidate::new(&root, &path);
+        if with_metrics {
+            candidate = candidate.with_metrics(&metrics);
+        }
+
+        assert!(matcher.needs_metrics());
+        assert_eq!(expected, matcher.evaluate(&candidate));
+    }
+
+    #[rstest(
+        path,
+        expected,
+        case::relative("Clients", "/Clients"),
+        case::slashes("/Clients/acme/", "/Clients/acme"),
+        case::root("/", ""),
+        case::empty("", "")
+    )]
+    fn normalizes_scope_paths(path: &str, expected: &str) {
+        assert_eq!(expected, scope_path(path));
+    }
+
+    fn index_and_pattern<'a>(specs: &[SpecRef<'a>]) -> Vec<(usize, &'a str)> {
+        specs.iter().map(|s| (s.index, s.pattern)).collect()
     }
 }
@@ -0,0 +1,106 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::Error;
+
+/// The measured extent of an entry, covering everything within it for directories.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metrics {
+    /// the size of a file or the total size of all files within a directory
+    pub size: u64,
+    /// the number of entries within a directory (recursively)
+    pub entries: u64,
+    /// the latest modification of the entry or anything within it
+    pub modified: SystemTime,
+}
+
+/// Measures entries on demand, keeping the metrics of every directory measured along the way, so
+/// a tree doesn't have to be measured again for any of its subdirectories.
+#[derive(Default)]
+pub struct MetricsCache {
+    cache: RefCell<HashMap<PathBuf, Metrics>>,
+}
+
+impl MetricsCache {
+    pub fn get(&self, path: &Path) -> Result<Metrics, Error> {
+        if let Some(metrics) = self.cache.borrow().get(path) {
+            return Ok(*metrics);
+        }
+
+        let metadata = fs::symlink_metadata(path).map_err(|e| Error::io(path, e))?;
+        let mut metrics = Metrics {
+            size: metadata.len(),
+            entries: 0,
+            modified: metadata.modified().map_err(|e| Error::io(path, e))?,
+        };
+
+        if !metadata.is_dir() {
+            return Ok(metrics);
+        }
+
+        metrics.size = 0;
+        for entry in fs::read_dir(path).map_err(|e| Error::io(path, e))? {
+            let entry = entry.map_err(|e| Error::io(path, e))?;
+            let entry_metrics = self.get(&entry.path())?;
+
+            metrics.size += entry_metrics.size;
+            metrics.entries += 1 + entry_metrics.entries;
+            metrics.modified = metrics.modified.max(entry_metrics.modified);
+        }
+
+        self.cache.borrow_mut().insert(path.to_path_buf(), metrics);
+
+        Ok(metrics)
+    }
+
+    /// Drops the metrics of the changed entry and all directories containing it.
+    pub fn invalidate(&self, path: &Path) {
+        let mut cache = self.cache.borrow_mut();
+        for ancestor in path.ancestors() {
+            cache.remove(ancestor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn measures_directories_recursively() {
+        let temp = TempDir::new().unwrap();
+        let directory = temp.path().join("cache");
+        fs::create_dir_all(directory.join("a/b")).unwrap();
+        fs::write(directory.join("a/one"), "1").unwrap();
+        fs::write(directory.join("a/b/two"), "22").unwrap();
+
+        let metrics_cache = MetricsCache::default();
+        let metrics = metrics_cache.get(&directory).unwrap();
+
+        assert_eq!(3, metrics.size);
+        assert_eq!(4, metrics.entries);
+        assert_eq!(2, metrics_cache.get(&directory.join("a/b")).unwrap().size);
+        assert_eq!(3, metrics_cache.cache.borrow().len());
+    }
+
+    #[test]
+    fn remeasures_after_invalidation() {
+        let temp = TempDir::new().unwrap();
+        let directory = temp.path().join("cache");
+        fs::create_dir_all(directory.join("a")).unwrap();
+        fs::write(directory.join("a/one"), "1").unwrap();
+
+        let metrics_cache = MetricsCache::default();
+        assert_eq!(1, metrics_cache.get(&directory).unwrap().size);
+
+        fs::write(directory.join("a/two"), "22").unwrap();
+        assert_eq!(1, metrics_cache.get(&directory).unwrap().size);
+
+        metrics_cache.invalidate(&directory.join("a/two"));
+        assert_eq!(3, metrics_cache.get(&directory).unwrap().size);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::ignore_list::IgnoreListFormat;
+
+/// The `.sync-exclude.lst` file of the Nextcloud desktop client.
+pub struct Nextcloud;
+
+impl IgnoreListFormat for Nextcloud {
+    const FILE_NAME: &'static str = ".sync-exclude.lst";
+
+    // a leading `/` anchors the pattern at the synced folder
+    fn pattern(relative_path: &str) -> String {
+        format!("/{}", relative_path)
+    }
+
+    // wildcards can't be escaped, they would match unrelated siblings as well
+    fn is_representable(relative_path: &str) -> bool {
+        !relative_path.contains(['*', '?', '[', ']'])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::IgnoreBackend;
+    use crate::error::Error;
+    use crate::ignore_list::IgnoreList;
+    use tempfile::TempDir;
+
+    #[test]
+    fn pattern() {
+        assert_eq!("/foo/build", Nextcloud::pattern("foo/build"));
+    }
+
+    #[test]
+    fn pattern_with_glob_characters() {
+        assert!(Nextcloud::is_representable("foo/build"));
+        assert!(!Nextcloud::is_representable("foo/[a]"));
+        assert!(!Nextcloud::is_representable("foo/*?"));
+    }
+
+    #[test]
+    fn rejects_paths_with_glob_characters() {
+        let temp = TempDir::new().unwrap();
+        let ignore_list = IgnoreList::<Nextcloud>::new(temp.path());
+
+        let result = ignore_list.ignore(&temp.path().join("foo/build*"));
+
+        assert!(matches!(result, Err(Error::Unrepresentable { .. })));
+        assert!(!temp.path().join(Nextcloud::FILE_NAME).exists());
+    }
+}
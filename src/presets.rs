@@ -0,0 +1,90 @@
+use config::{Config, FileFormat};
+
+use crate::configuration::MatcherConfig;
+use crate::error::Error;
+
+/// A named set of specs for a common ecosystem, compiled into the binary.
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// the specs in the form of the `matcher` section of the configuration
+    pub specs: &'static str,
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "rust",
+        description: "Cargo build output",
+        specs: include_str!("../presets/rust.yml"),
+    },
+    Preset {
+        name: "node",
+        description: "Node.js dependencies and bundler caches",
+        specs: include_str!("../presets/node.yml"),
+    },
+    Preset {
+        name: "maven",
+        description: "Maven build output",
+        specs: include_str!("../presets/maven.yml"),
+    },
+    Preset {
+        name: "gradle",
+        description: "Gradle caches and build output",
+        specs: include_str!("../presets/gradle.yml"),
+    },
+    Preset {
+        name: "python",
+        description: "Python bytecode, virtual environments and tool caches",
+        specs: include_str!("../presets/python.yml"),
+    },
+    Preset {
+        name: "latex",
+        description: "LaTeX auxiliary files",
+        specs: include_str!("../presets/latex.yml"),
+    },
+    Preset {
+        name: "unity",
+        description: "Unity generated project folders",
+        specs: include_str!("../presets/unity.yml"),
+    },
+    Preset {
+        name: "xcode",
+        description: "Xcode derived data and user state",
+        specs: include_str!("../presets/xcode.yml"),
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name == name)
+}
+
+impl Preset {
+    pub fn matcher_config(&self) -> Result<MatcherConfig, Error> {
+        let mut config = Config::new();
+        config.merge(config::File::from_str(self.specs, FileFormat::Yaml))?;
+
+        Ok(config.try_into()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::Matcher;
+
+    #[test]
+    fn presets_are_valid() {
+        for preset in PRESETS {
+            let matcher_config = preset.matcher_config().unwrap();
+
+            assert!(!matcher_config.ignore_specs.as_ref().unwrap().is_empty());
+            Matcher::new(&Some(matcher_config)).unwrap();
+        }
+    }
+
+    #[test]
+    fn finds_presets_by_name() {
+        assert_eq!("rust", find("rust").unwrap().name);
+        assert!(find("cobol").is_none());
+    }
+}
@@ -1,82 +1,196 @@
+use std::collections::BTreeMap;
+use std::fs::{self, FileType};
 use std::path::{Path, PathBuf};
-use std::process;
 use std::sync::mpsc::channel;
 use std::time::Duration;
 
-use log::{debug, error, info, warn};
+use log::{debug, info, warn};
 use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
 use walkdir::WalkDir;
 
-use crate::dropbox::Dropbox;
-use crate::matcher::Matcher;
+use crate::backend::{IgnoreBackend, IgnoreState};
+use crate::configuration::EntryType;
+use crate::conflict;
+use crate::error::Error;
+use crate::git::{GitExcludes, EXCLUDE_FILE_PATH};
+use crate::ignore_file::{FileMatch, IgnoreFiles, DROPIGNORE_FILE_NAME};
+use crate::matcher::{Candidate, MatchResult, Matcher};
+use crate::metrics::MetricsCache;
 
 pub struct Scanner {
-    dropbox: Dropbox,
+    backend: Box<dyn IgnoreBackend>,
     matcher: Matcher,
+    dropignore_files: IgnoreFiles,
+    git_excludes: Option<GitExcludes>,
+    metrics: MetricsCache,
+    // the root folder scope paths are relative to, otherwise the scanned directory
+    scope_root: Option<PathBuf>,
 }
 
 #[derive(Default)]
 struct ScannerStats {
     known_ignores: u32,
     new_ignores: u32,
+    unignores: u32,
+    foreign_ignores: u32,
+    adopted_ignores: u32,
+    repairs: u32,
+    orphans: u32,
+    deleted_conflicts: u32,
+    merged_conflicts: u32,
+    kept_conflict_entries: u32,
+    reported_conflicts: u32,
+    errors: u32,
 }
 
 impl Scanner {
-    pub fn new(matcher: Matcher, dropbox: Dropbox) -> Result<Self, String> {
-        Ok(Scanner { dropbox, matcher })
+    pub fn new(matcher: Matcher, backend: Box<dyn IgnoreBackend>) -> Result<Self, Error> {
+        Ok(Scanner {
+            backend,
+            matcher,
+            dropignore_files: IgnoreFiles::new(DROPIGNORE_FILE_NAME),
+            git_excludes: None,
+            metrics: Default::default(),
+            scope_root: None,
+        })
     }
 
-    pub fn scan(&self, directory_in: PathBuf, watch: bool, dry_run: bool) {
-        if !directory_in.exists() {
-            error!(
-                "The given directory {:?} does not exist! Exiting.",
-                directory_in
-            );
-            return;
-        }
-        let directory = directory_in.canonicalize().unwrap();
+    /// Resolves scope paths against the given root folder (e.g. the Dropbox folder), so they
+    /// apply the same when scanning a directory below it.
+    pub fn with_scope_root(mut self, scope_root: PathBuf) -> Self {
+        self.scope_root = Some(scope_root);
+        self
+    }
+
+    /// Also applies the ignore rules of git within git work trees.
+    pub fn with_git_excludes(mut self, git_excludes: GitExcludes) -> Self {
+        self.git_excludes = Some(git_excludes);
+        self
+    }
+
+    pub fn scan(
+        &self,
+        directory_in: PathBuf,
+        watch: bool,
+        dry_run: bool,
+        reconcile: bool,
+    ) -> Result<(), Error> {
+        let directory = directory_in
+            .canonicalize()
+            .map_err(|e| Error::io(&directory_in, e))?;
         info!("{:8}{:?}", if watch { "WATCH" } else { "SCAN" }, directory);
 
         let mut scanner_stats: ScannerStats = Default::default();
 
         // even if we are watching, always perform a scan before
-        let walker = WalkDir::new(&directory).into_iter();
-        for _entry in
-            walker.filter_entry(|e| Self::handle_entry(self, e.path(), dry_run, &mut scanner_stats))
-        {
-        }
+        Self::walk(&directory, &mut scanner_stats, |path, file_type, stats| {
+            Self::handle_entry(
+                self,
+                &directory,
+                path,
+                Some(file_type),
+                dry_run,
+                reconcile,
+                stats,
+            )
+        });
 
         if watch {
             let (sender, receiver) = channel();
 
-            let mut watcher: RecommendedWatcher =
-                Watcher::new(sender, Duration::from_secs(2)).unwrap();
+            let mut watcher: RecommendedWatcher = Watcher::new(sender, Duration::from_secs(2))?;
+            watcher.watch(&directory, RecursiveMode::Recursive)?;
 
-            let watch_result = watcher.watch(&directory, RecursiveMode::Recursive);
-            if watch_result.is_err() {
-                error!(
-                    "Failed watching {:?}: {:?}",
-                    directory,
-                    watch_result.err().unwrap()
-                );
-                process::exit(1);
-            }
-
-            loop {
-                match receiver.recv() {
-                    Ok(event) => {
-                        // Chmod: creating multiple dirs at once (e.g. `mkdir -p`) first is `Create`, others `Chmod`
-                        match event {
-                            DebouncedEvent::Create(p)
-                            | DebouncedEvent::Chmod(p)
-                            | DebouncedEvent::Rename(_, p)
-                            | DebouncedEvent::Write(p) => {
-                                Self::handle_entry(self, p.as_path(), dry_run, &mut scanner_stats);
-                            }
-                            _ => {}
-                        }
+            // only ends when the watcher hung up
+            while let Ok(event) = receiver.recv() {
+                // any change affects the metrics of the containing directories
+                let changed = match &event {
+                    DebouncedEvent::Rename(from, to) => vec![from.clone(), to.clone()],
+                    DebouncedEvent::Create(p)
+                    | DebouncedEvent::Chmod(p)
+                    | DebouncedEvent::Write(p)
+                    | DebouncedEvent::Remove(p) => vec![p.clone()],
+                    _ => vec![],
+                };
+                for p in &changed {
+                    self.metrics.invalidate(p);
+                }
+
+                // Chmod: creating multiple dirs at once (e.g. `mkdir -p`) first is `Create`, others `Chmod`
+                match event {
+                    DebouncedEvent::Create(p)
+                    | DebouncedEvent::Chmod(p)
+                    | DebouncedEvent::Rename(_, p)
+                    | DebouncedEvent::Write(p)
+                        if self.is_rules_file(&p) =>
+                    {
+                        Self::handle_rules_file_change(
+                            self,
+                            &directory,
+                            &p,
+                            dry_run,
+                            reconcile,
+                            &mut scanner_stats,
+                        );
+                    }
+                    DebouncedEvent::Remove(p) | DebouncedEvent::Rename(p, _)
+                        if self.is_rules_file(&p) =>
+                    {
+                        Self::handle_rules_file_change(
+                            self,
+                            &directory,
+                            &p,
+                            dry_run,
+                            reconcile,
+                            &mut scanner_stats,
+                        );
+                    }
+                    DebouncedEvent::Create(p)
+                    | DebouncedEvent::Rename(_, p)
+                    | DebouncedEvent::Remove(p)
+                        if self.matcher.is_marker(&p) =>
+                    {
+                        Self::handle_marker_change(
+                            self,
+                            &directory,
+                            &p,
+                            dry_run,
+                            reconcile,
+                            &mut scanner_stats,
+                        );
+                    }
+                    DebouncedEvent::Create(p)
+                    | DebouncedEvent::Chmod(p)
+                    | DebouncedEvent::Rename(_, p)
+                    | DebouncedEvent::Write(p) => {
+                        let result = Self::handle_entry(
+                            self,
+                            &directory,
+                            &p,
+                            None,
+                            dry_run,
+                            reconcile,
+                            &mut scanner_stats,
+                        );
+                        Self::handle_error(result, &mut scanner_stats);
+                    }
+                    DebouncedEvent::Error(e, _) => {
+                        Self::handle_error::<()>(Err(e.into()), &mut scanner_stats);
+                    }
+                    _ => {}
+                }
+
+                if self.matcher.needs_metrics() {
+                    for p in &changed {
+                        Self::handle_metrics_change(
+                            self,
+                            &directory,
+                            p,
+                            dry_run,
+                            reconcile,
+                            &mut scanner_stats,
+                        );
                     }
-                    Err(error) => warn!("Watch error {:?}", error),
                 }
             }
         }
@@ -85,37 +199,1553 @@ impl Scanner {
             "Finished with {} known and {} new ignores.",
             scanner_stats.known_ignores, scanner_stats.new_ignores
         );
+        if reconcile {
+            info!(
+                "Reconciled {} adopted and {} foreign ignores and {} unignores.",
+                scanner_stats.adopted_ignores,
+                scanner_stats.foreign_ignores,
+                scanner_stats.unignores
+            );
+        }
+        Self::log_errors(&scanner_stats);
+
+        Ok(())
+    }
+
+    pub fn unignore(&self, paths: &[PathBuf], dry_run: bool) -> Result<(), Error> {
+        let mut scanner_stats: ScannerStats = Default::default();
+
+        for path_in in paths {
+            let result = path_in
+                .canonicalize()
+                .map_err(|e| Error::io(path_in, e))
+                .and_then(|path| Self::handle_unignore(self, &path, dry_run, &mut scanner_stats));
+            Self::handle_error(result, &mut scanner_stats);
+        }
+
+        info!("Finished with {} unignores.", scanner_stats.unignores);
+        Self::log_errors(&scanner_stats);
+
+        Ok(())
+    }
+
+    pub fn unignore_matching(&self, directory_in: PathBuf, dry_run: bool) -> Result<(), Error> {
+        let directory = directory_in
+            .canonicalize()
+            .map_err(|e| Error::io(&directory_in, e))?;
+        info!("{:8}{:?}", "SCAN", directory);
+
+        let mut scanner_stats: ScannerStats = Default::default();
+
+        Self::walk(&directory, &mut scanner_stats, |path, file_type, stats| {
+            Self::handle_matching_unignore_entry(self, &directory, path, file_type, dry_run, stats)
+        });
+
+        info!("Finished with {} unignores.", scanner_stats.unignores);
+        Self::log_errors(&scanner_stats);
+
+        Ok(())
+    }
+
+    pub fn list(&self, directory_in: PathBuf) -> Result<(), Error> {
+        let directory = directory_in
+            .canonicalize()
+            .map_err(|e| Error::io(&directory_in, e))?;
+        info!("{:8}{:?}", "LIST", directory);
+
+        let mut scanner_stats: ScannerStats = Default::default();
+
+        Self::walk(&directory, &mut scanner_stats, |path, file_type, stats| {
+            Self::handle_list_entry(self, &directory, path, file_type, stats)
+        });
+
+        info!(
+            "Finished with {} managed and {} foreign ignores.",
+            scanner_stats.known_ignores, scanner_stats.foreign_ignores
+        );
+        Self::log_errors(&scanner_stats);
+
+        Ok(())
+    }
+
+    /// Explains why the path is ignored or not: the specs and rules involved, anything keeping
+    /// scans from reaching it and its current ignore state. Patterns are anchored to the first
+    /// of the directories containing the path, otherwise to its parent.
+    pub fn explain(&self, directories: &[PathBuf], path_in: &Path) -> Result<(), Error> {
+        let path = path_in.canonicalize().map_err(|e| Error::io(path_in, e))?;
+        let root = directories
+            .iter()
+            .filter_map(|d| d.canonicalize().ok())
+            .find(|d| path.starts_with(d))
+            .or_else(|| path.parent().map(Path::to_path_buf))
+            .unwrap_or_else(|| path.clone());
+        info!("{:8}{:?}", "EXPLAIN", path);
+        info!("  {:8}{:?}", "ROOT", root);
+
+        let candidate = self.candidate(&root, &path, None)?;
+        let report = self.matcher.report(&candidate);
+        let file_match = self.file_match(&root, &path)?;
+
+        let result = match self.evaluate(&root, &path, None)? {
+            MatchResult::Ignore => "ignore",
+            MatchResult::Skip => "skip (overridden by a skip-spec or rule)",
+            MatchResult::NoMatch => "no match",
+        };
+        info!("  {:8}{}", "RESULT", result);
+
+        for spec in &report.ignore_specs {
+            info!("  {:8}{}", "SPEC", spec);
+        }
+        if let Some(file_match) = &file_match {
+            info!(
+                "  {:8}{:?} in {:?}{}",
+                "FILE",
+                file_match.pattern,
+                file_match.file,
+                if file_match.ignore {
+                    ""
+                } else {
+                    " (re-including)"
+                }
+            );
+        }
+        let skip_specs = match &file_match {
+            Some(FileMatch { ignore: true, .. }) => {
+                self.matcher.skip_spec(&candidate).into_iter().collect()
+            }
+            Some(_) => vec![],
+            None => report.skip_specs,
+        };
+        for spec in skip_specs {
+            info!("  {:8}{} (overriding any ignore)", "SKIP", spec);
+        }
+        if let (Some(rule), None) = (&report.rule, &file_match) {
+            info!("  {:8}{} (the last matching rule)", "DECIDED", rule);
+        }
+        if let (Some(scope), None) = (&report.scope, &file_match) {
+            info!("  {:8}{} (deciding)", "SCOPE", scope);
+        }
+
+        for ancestor in path.ancestors().skip(1) {
+            if self.backend.is_ignored(ancestor)? {
+                info!("  {:8}{:?} (ignored already)", "INSIDE", ancestor);
+            }
+            if ancestor == root {
+                break;
+            }
+            if !Self::recurse(ancestor) {
+                info!(
+                    "  {:8}{:?} (not descended into by scans)",
+                    "DOTDIR", ancestor
+                );
+            }
+        }
+
+        let state = self.backend.ignore_state(&path)?;
+        if self.backend.is_managed(&path)? {
+            info!("  {:8}{:?} (managed by dropignore)", "STATE", state);
+        } else {
+            info!("  {:8}{:?}", "STATE", state);
+        }
+
+        Ok(())
+    }
+
+    pub fn repair(&self, directory_in: PathBuf, dry_run: bool) -> Result<(), Error> {
+        let directory = directory_in
+            .canonicalize()
+            .map_err(|e| Error::io(&directory_in, e))?;
+        info!("{:8}{:?}", "REPAIR", directory);
+
+        let mut scanner_stats: ScannerStats = Default::default();
+
+        Self::walk(&directory, &mut scanner_stats, |path, _, stats| {
+            Self::handle_repair_entry(self, path, dry_run, stats)
+        });
+
+        info!("Finished with {} repairs.", scanner_stats.repairs);
+        Self::log_errors(&scanner_stats);
+
+        Ok(())
+    }
+
+    /// Reports the conflicting copies, only deleting or merging them as explicitly requested.
+    pub fn conflicts(
+        &self,
+        directory_in: PathBuf,
+        delete: bool,
+        merge: bool,
+        dry_run: bool,
+    ) -> Result<(), Error> {
+        let directory = directory_in
+            .canonicalize()
+            .map_err(|e| Error::io(&directory_in, e))?;
+        info!("{:8}{:?}", "SCAN", directory);
+
+        let mut scanner_stats: ScannerStats = Default::default();
+        let mut conflicts = Vec::new();
+
+        // only collect while walking, resolving changes the directories being walked
+        Self::walk(&directory, &mut scanner_stats, |path, file_type, _| {
+            Self::handle_conflict_entry(self, &directory, path, file_type, &mut conflicts)
+        });
+
+        for (path, original) in conflicts {
+            let result = self
+                .matches(&directory, &original, None)
+                .and_then(|original_ignored| {
+                    // copies of ignored originals get deleted, any other copies merged
+                    let resolve = if original_ignored { delete } else { merge };
+                    Self::handle_conflict(
+                        &path,
+                        &original,
+                        original_ignored,
+                        resolve,
+                        dry_run,
+                        &mut scanner_stats,
+                    )
+                });
+            Self::handle_error(result, &mut scanner_stats);
+        }
+
+        info!(
+            "Finished with {} deleted and {} merged conflicts ({} entries kept), {} only reported.",
+            scanner_stats.deleted_conflicts,
+            scanner_stats.merged_conflicts,
+            scanner_stats.kept_conflict_entries,
+            scanner_stats.reported_conflicts
+        );
+        Self::log_errors(&scanner_stats);
+
+        Ok(())
+    }
+
+    /// Reports the orphans grouped by their parent directory and unignores the ones at or below
+    /// any of the given paths, e.g. a single orphan or the parent directory of a group.
+    pub fn orphans(
+        &self,
+        directory_in: PathBuf,
+        unignore_in: &[PathBuf],
+        dry_run: bool,
+    ) -> Result<(), Error> {
+        let directory = directory_in
+            .canonicalize()
+            .map_err(|e| Error::io(&directory_in, e))?;
+        let unignore = unignore_in
+            .iter()
+            .map(|p| p.canonicalize().map_err(|e| Error::io(p, e)))
+            .collect::<Result<Vec<PathBuf>, Error>>()?;
+        info!("{:8}{:?}", "SCAN", directory);
+
+        let mut scanner_stats: ScannerStats = Default::default();
+        let mut orphans: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+
+        Self::walk(&directory, &mut scanner_stats, |path, file_type, _| {
+            Self::handle_orphan_entry(self, &directory, path, file_type, &mut orphans)
+        });
+
+        for (parent, paths) in &orphans {
+            info!("{:8}{:?}", "PARENT", parent);
+
+            for path in paths {
+                let name = path.file_name().unwrap_or_default();
+                match self
+                    .matcher
+                    .skip_spec(&self.candidate(&directory, path, None)?)
+                {
+                    Some(spec) => {
+                        info!("  {:8}{:?} (contradicted by {})", "ORPHAN", name, spec)
+                    }
+                    None => info!("  {:8}{:?}", "ORPHAN", name),
+                }
+                scanner_stats.orphans += 1;
+            }
+        }
+
+        for path in orphans.values().flatten() {
+            if unignore.iter().any(|u| path.starts_with(u)) {
+                let result = Self::handle_unignore(self, path, dry_run, &mut scanner_stats);
+                Self::handle_error(result, &mut scanner_stats);
+            }
+        }
+
+        info!(
+            "Finished with {} orphans in {} directories and {} unignores.",
+            scanner_stats.orphans,
+            orphans.len(),
+            scanner_stats.unignores
+        );
+        Self::log_errors(&scanner_stats);
+
+        Ok(())
+    }
+
+    /// Walks the given directory, only descending into entries the handler agrees to. The handler
+    /// gets the file type read while walking, so entries don't have to be stat-ed again.
+    fn walk(
+        directory: &Path,
+        scanner_stats: &mut ScannerStats,
+        mut handler: impl FnMut(&Path, FileType, &mut ScannerStats) -> Result<bool, Error>,
+    ) {
+        let mut walk_errors = Vec::new();
+
+        let walker = WalkDir::new(directory).into_iter();
+        for entry in walker.filter_entry(|e| {
+            let result = handler(e.path(), e.file_type(), scanner_stats);
+            Self::handle_error(result, scanner_stats).unwrap_or(false)
+        }) {
+            if let Err(e) = entry {
+                walk_errors.push(Error::from(e));
+            }
+        }
+
+        for e in walk_errors {
+            Self::handle_error::<()>(Err(e), scanner_stats);
+        }
+    }
+
+    fn handle_error<T>(result: Result<T, Error>, scanner_stats: &mut ScannerStats) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("{}", e);
+                scanner_stats.errors += 1;
+                None
+            }
+        }
+    }
+
+    fn log_errors(scanner_stats: &ScannerStats) {
+        if scanner_stats.errors > 0 {
+            warn!("Encountered {} errors.", scanner_stats.errors);
+        }
+    }
+
+    /// Evaluates the path against the `.dropignore` files first, then the ignore rules of git
+    /// (which can only add ignores) and finally the global configuration. A matching skip-spec
+    /// wins over all of them.
+    fn evaluate(
+        &self,
+        root: &Path,
+        path: &Path,
+        file_type: Option<FileType>,
+    ) -> Result<MatchResult, Error> {
+        let candidate = self.candidate(root, path, file_type)?;
+
+        match self.file_match(root, path)? {
+            Some(FileMatch { ignore: false, .. }) => Ok(MatchResult::NoMatch),
+            Some(_) => match self.matcher.skip_spec(&candidate) {
+                Some(_) => Ok(MatchResult::Skip),
+                None => Ok(MatchResult::Ignore),
+            },
+            None => Ok(self.matcher.evaluate(&candidate)),
+        }
+    }
+
+    /// Returns the deciding rule of the `.dropignore` files within the root or the ignore rules
+    /// of git, if any.
+    fn file_match(&self, root: &Path, path: &Path) -> Result<Option<FileMatch>, Error> {
+        let file_match = self.dropignore_files.evaluate(path, root)?;
+        if file_match.is_some() {
+            return Ok(file_match);
+        }
+
+        match &self.git_excludes {
+            Some(git_excludes) => Ok(git_excludes.evaluate(path)?.filter(|m| m.ignore)),
+            None => Ok(None),
+        }
     }
 
-    fn handle_entry(&self, path: &Path, dry_run: bool, scanner_stats: &mut ScannerStats) -> bool {
-        let matches = self.matcher.matches(path.to_str().unwrap().to_string());
-        if matches {
-            if self.dropbox.is_ignored(path) {
+    /// Builds the candidate of the path, only stat-ing it for its entry type if its file type is
+    /// unknown, e.g. for watcher events.
+    fn candidate<'a>(
+        &'a self,
+        root: &Path,
+        path: &'a Path,
+        file_type: Option<FileType>,
+    ) -> Result<Candidate<'a>, Error> {
+        let mut candidate = Candidate::new(root, path);
+        if let Some(scope_root) = &self.scope_root {
+            candidate = candidate.with_scope_root(scope_root);
+        }
+        if self.matcher.needs_entry_type() {
+            let file_type = match file_type {
+                Some(file_type) => file_type,
+                None => fs::symlink_metadata(path)
+                    .map_err(|e| Error::io(path, e))?
+                    .file_type(),
+            };
+            candidate = candidate.with_entry_type(Self::entry_type(file_type));
+        }
+        if self.matcher.needs_metrics() {
+            candidate = candidate.with_metrics(&self.metrics);
+        }
+
+        Ok(candidate)
+    }
+
+    fn entry_type(file_type: FileType) -> EntryType {
+        if file_type.is_symlink() {
+            EntryType::Symlink
+        } else if file_type.is_dir() {
+            EntryType::Dir
+        } else if file_type.is_file() {
+            EntryType::File
+        } else {
+            EntryType::Any
+        }
+    }
+
+    fn matches(
+        &self,
+        root: &Path,
+        path: &Path,
+        file_type: Option<FileType>,
+    ) -> Result<bool, Error> {
+        Ok(self.evaluate(root, path, file_type)? == MatchResult::Ignore)
+    }
+
+    fn is_rules_file(&self, path: &Path) -> bool {
+        path.file_name()
+            .map(|n| n == DROPIGNORE_FILE_NAME)
+            .unwrap_or(false)
+            || (self.git_excludes.is_some() && GitExcludes::is_rules_file(path))
+    }
+
+    /// Reloads the changed rules file and rescans its directory with the new rules.
+    fn handle_rules_file_change(
+        &self,
+        root: &Path,
+        path: &Path,
+        dry_run: bool,
+        reconcile: bool,
+        scanner_stats: &mut ScannerStats,
+    ) {
+        let directory = match path.parent() {
+            Some(directory) => directory,
+            None => return,
+        };
+
+        info!("{:8}{:?}", "RELOAD", path);
+        self.dropignore_files.invalidate(directory);
+
+        // `.git/info/exclude` applies to the whole work tree
+        let directory = match &self.git_excludes {
+            Some(git_excludes) if path.ends_with(EXCLUDE_FILE_PATH) => {
+                let work_tree = directory.ancestors().nth(2).unwrap_or(directory);
+                git_excludes.invalidate(work_tree);
+                work_tree
+            }
+            Some(git_excludes) => {
+                git_excludes.invalidate(directory);
+                directory
+            }
+            None => directory,
+        };
+
+        Self::walk(directory, scanner_stats, |path, file_type, stats| {
+            Self::handle_entry(self, root, path, Some(file_type), dry_run, reconcile, stats)
+        });
+    }
+
+    /// Rescans the directory of an appeared or removed marker, covering the directory itself
+    /// and every entry beside or below the marker.
+    fn handle_marker_change(
+        &self,
+        root: &Path,
+        path: &Path,
+        dry_run: bool,
+        reconcile: bool,
+        scanner_stats: &mut ScannerStats,
+    ) {
+        let directory = match path.parent() {
+            Some(directory) => directory,
+            None => return,
+        };
+
+        info!("{:8}{:?}", "MARKER", path);
+        Self::walk(directory, scanner_stats, |path, file_type, stats| {
+            Self::handle_entry(self, root, path, Some(file_type), dry_run, reconcile, stats)
+        });
+    }
+
+    /// Reevaluates the directories containing the changed entry from the root downwards, as
+    /// their metrics changed along with it. Stops at the first one not to descend into, e.g.
+    /// because it got ignored.
+    fn handle_metrics_change(
+        &self,
+        root: &Path,
+        path: &Path,
+        dry_run: bool,
+        reconcile: bool,
+        scanner_stats: &mut ScannerStats,
+    ) {
+        let directories: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|a| a.starts_with(root) && *a != root)
+            .collect();
+
+        for directory in directories.into_iter().rev() {
+            let result = Self::handle_entry(
+                self,
+                root,
+                directory,
+                None,
+                dry_run,
+                reconcile,
+                scanner_stats,
+            );
+            if !Self::handle_error(result, scanner_stats).unwrap_or(false) {
+                break;
+            }
+        }
+    }
+
+    fn handle_entry(
+        &self,
+        root: &Path,
+        path: &Path,
+        file_type: Option<FileType>,
+        dry_run: bool,
+        reconcile: bool,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<bool, Error> {
+        let descend = Self::handle_single_entry(
+            self,
+            root,
+            path,
+            file_type,
+            dry_run,
+            reconcile,
+            scanner_stats,
+        )?;
+
+        // the state on disk has to mirror the configuration below such entries as well
+        if reconcile && !descend {
+            Self::reconcile_below(self, root, path, dry_run, scanner_stats);
+        }
+
+        Ok(descend)
+    }
+
+    /// Handles the entry itself, returning whether to descend into it.
+    fn handle_single_entry(
+        &self,
+        root: &Path,
+        path: &Path,
+        file_type: Option<FileType>,
+        dry_run: bool,
+        reconcile: bool,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<bool, Error> {
+        let match_result = self.evaluate(root, path, file_type)?;
+        if match_result == MatchResult::Ignore {
+            if self.backend.is_ignored(path)? {
                 debug!("KNOWN   {:?}", path);
                 scanner_stats.known_ignores += 1;
-                return false;
+
+                if reconcile && !self.backend.is_managed(path)? {
+                    Self::handle_adopt(self, path, dry_run, scanner_stats)?;
+                }
+
+                return Ok(false);
             }
 
             if dry_run {
                 info!("IGNORE  {:?}", path);
-            } else if self.dropbox.ignore(path) {
-                info!("IGNORED {:?}", path);
             } else {
-                warn!("Failed ignoring {:?}", path);
+                self.backend.ignore(path)?;
+                info!("IGNORED {:?}", path);
             }
 
             scanner_stats.new_ignores += 1;
-            return false;
+            return Ok(false);
         }
 
-        // don't recurse dot-entries (only effective in "scan" mode)
-        let recurse = path
+        if reconcile && self.backend.is_ignored(path)? {
+            if !self.backend.is_managed(path)? {
+                debug!("FOREIGN {:?} (not ignored by dropignore)", path);
+                scanner_stats.foreign_ignores += 1;
+            } else {
+                Self::handle_stale_ignore(self, path, match_result, dry_run, scanner_stats)?;
+            }
+        }
+
+        Ok(Self::recurse(path))
+    }
+
+    /// Unignores the managed ignores below an entry scans don't descend into, i.e. an ignored
+    /// entry or a dot-directory, which aren't matching anymore. Nothing gets ignored there.
+    fn reconcile_below(
+        &self,
+        root: &Path,
+        directory: &Path,
+        dry_run: bool,
+        scanner_stats: &mut ScannerStats,
+    ) {
+        Self::walk(directory, scanner_stats, |path, file_type, stats| {
+            if path == directory
+                || !self.backend.is_ignored(path)?
+                || !self.backend.is_managed(path)?
+            {
+                return Ok(true);
+            }
+
+            let match_result = self.evaluate(root, path, Some(file_type))?;
+            if match_result != MatchResult::Ignore {
+                Self::handle_stale_ignore(self, path, match_result, dry_run, stats)?;
+            }
+
+            Ok(true)
+        });
+    }
+
+    fn handle_stale_ignore(
+        &self,
+        path: &Path,
+        match_result: MatchResult,
+        dry_run: bool,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<(), Error> {
+        let reason = match match_result {
+            MatchResult::Skip => "overridden by a skip-spec",
+            _ => "not matching any ignore-spec anymore",
+        };
+
+        if dry_run {
+            info!("UNIGNORE  {:?} ({})", path, reason);
+        } else {
+            self.backend.unignore(path)?;
+            info!("UNIGNORED {:?} ({})", path, reason);
+        }
+
+        scanner_stats.unignores += 1;
+        Ok(())
+    }
+
+    fn handle_adopt(
+        &self,
+        path: &Path,
+        dry_run: bool,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<(), Error> {
+        let reason = "already ignored and matching an ignore-spec";
+
+        if dry_run {
+            info!("ADOPT   {:?} ({})", path, reason);
+        } else {
+            self.backend.manage(path)?;
+            info!("ADOPTED {:?} ({})", path, reason);
+        }
+
+        scanner_stats.adopted_ignores += 1;
+        Ok(())
+    }
+
+    fn handle_matching_unignore_entry(
+        &self,
+        root: &Path,
+        path: &Path,
+        file_type: FileType,
+        dry_run: bool,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<bool, Error> {
+        if self.matches(root, path, Some(file_type))? {
+            Self::handle_unignore(self, path, dry_run, scanner_stats)?;
+            return Ok(false);
+        }
+
+        Ok(Self::recurse(path))
+    }
+
+    fn handle_list_entry(
+        &self,
+        root: &Path,
+        path: &Path,
+        file_type: FileType,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<bool, Error> {
+        if !self.backend.is_ignored(path)? {
+            return Ok(Self::recurse(path));
+        }
+
+        // ignored entries are neither listed nor synced below, so don't descend
+        if self.matches(root, path, Some(file_type))? {
+            info!("MANAGED {:?}", path);
+            scanner_stats.known_ignores += 1;
+        } else {
+            info!("FOREIGN {:?}", path);
+            scanner_stats.foreign_ignores += 1;
+        }
+
+        Ok(false)
+    }
+
+    fn handle_repair_entry(
+        &self,
+        path: &Path,
+        dry_run: bool,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<bool, Error> {
+        let state = self.backend.ignore_state(path)?;
+        let reason = match state {
+            IgnoreState::NotIgnored => return Ok(Self::recurse(path)),
+            IgnoreState::Ignored => return Ok(false),
+            IgnoreState::Partial => "partially ignored",
+            IgnoreState::Invalid => "invalid or leftover ignore markers",
+        };
+
+        if dry_run {
+            info!("REPAIR   {:?} ({})", path, reason);
+        } else {
+            self.backend.repair(path)?;
+            info!("REPAIRED {:?} ({})", path, reason);
+        }
+
+        scanner_stats.repairs += 1;
+
+        // decided by the repaired state, as a dry run leaves the entry as it is
+        Ok(state.repaired() == IgnoreState::NotIgnored && Self::recurse(path))
+    }
+
+    fn handle_orphan_entry(
+        &self,
+        root: &Path,
+        path: &Path,
+        file_type: FileType,
+        orphans: &mut BTreeMap<PathBuf, Vec<PathBuf>>,
+    ) -> Result<bool, Error> {
+        if !self.backend.is_ignored(path)? {
+            return Ok(Self::recurse(path));
+        }
+
+        if !self.matches(root, path, Some(file_type))? {
+            let parent = path.parent().unwrap_or(path).to_path_buf();
+            orphans.entry(parent).or_default().push(path.to_path_buf());
+        }
+
+        // ignored entries are not synced at all, so don't descend
+        Ok(false)
+    }
+
+    fn handle_conflict_entry(
+        &self,
+        root: &Path,
+        path: &Path,
+        file_type: FileType,
+        conflicts: &mut Vec<(PathBuf, PathBuf)>,
+    ) -> Result<bool, Error> {
+        let original_name = path
             .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(conflict::original_name);
+
+        if let Some(original_name) = original_name {
+            conflicts.push((path.to_path_buf(), path.with_file_name(original_name)));
+            return Ok(false);
+        }
+
+        // ignored entries aren't synced, so there are no conflicts below
+        if self.matches(root, path, Some(file_type))? {
+            return Ok(false);
+        }
+
+        Ok(Self::recurse(path))
+    }
+
+    fn handle_conflict(
+        path: &Path,
+        original: &Path,
+        original_ignored: bool,
+        resolve: bool,
+        dry_run: bool,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<(), Error> {
+        if original_ignored {
+            let reason = "original matching an ignore-spec";
+
+            if !resolve {
+                info!("CONFLICT {:?} ({}, delete with '--delete')", path, reason);
+                scanner_stats.reported_conflicts += 1;
+                return Ok(());
+            }
+
+            if dry_run {
+                info!("DELETE  {:?} ({})", path, reason);
+            } else {
+                conflict::delete(path)?;
+                info!("DELETED {:?} ({})", path, reason);
+            }
+
+            scanner_stats.deleted_conflicts += 1;
+            return Ok(());
+        }
+
+        if !resolve {
+            info!(
+                "CONFLICT {:?} (merge into {:?} with '--merge')",
+                path, original
+            );
+            scanner_stats.reported_conflicts += 1;
+            return Ok(());
+        }
+
+        let kept = conflict::merge(path, original, dry_run)?;
+        for kept_path in &kept {
+            warn!("KEPT    {:?} (differing from the original)", kept_path);
+        }
+
+        if dry_run {
+            info!("MERGE   {:?} into {:?}", path, original);
+        } else {
+            info!("MERGED  {:?} into {:?}", path, original);
+        }
+
+        scanner_stats.merged_conflicts += 1;
+        scanner_stats.kept_conflict_entries += kept.len() as u32;
+        Ok(())
+    }
+
+    fn handle_unignore(
+        &self,
+        path: &Path,
+        dry_run: bool,
+        scanner_stats: &mut ScannerStats,
+    ) -> Result<(), Error> {
+        if !self.backend.is_ignored(path)? {
+            debug!("UNKNOWN {:?}", path);
+            return Ok(());
+        }
+
+        if dry_run {
+            info!("UNIGNORE  {:?}", path);
+        } else {
+            self.backend.unignore(path)?;
+            info!("UNIGNORED {:?}", path);
+        }
+
+        scanner_stats.unignores += 1;
+        Ok(())
+    }
+
+    fn recurse(path: &Path) -> bool {
+        // don't recurse dot-entries (only effective in "scan" mode)
+        path.file_name()
             .unwrap_or_default()
             .to_str()
             .map(|s| !s.starts_with('.'))
-            .unwrap_or(true);
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::configuration::{MatcherConfig, MatcherSpec, ScopeConfig};
+    use rstest::rstest;
+    use std::fs;
+    use tempfile::{Builder, TempDir};
+
+    #[test]
+    fn scan_ignores_matching_entries() {
+        let temp = arrange_directories(&["a/build", "a/src/build", "b/target/build", "c"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone())).unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(backend.is_ignored(&directory.join("a/build")).unwrap());
+        assert!(!backend.is_ignored(&directory.join("a/src/build")).unwrap());
+        assert!(backend.is_ignored(&directory.join("b/target")).unwrap());
+        assert!(!backend
+            .is_ignored(&directory.join("b/target/build"))
+            .unwrap());
+        assert!(!backend.is_ignored(&directory.join("c")).unwrap());
+    }
+
+    #[test]
+    fn scan_with_dry_run_ignores_nothing() {
+        let temp = arrange_directories(&["a/build"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone())).unwrap();
+        scanner.scan(directory.clone(), false, true, false).unwrap();
+
+        assert!(!backend.is_ignored(&directory.join("a/build")).unwrap());
+    }
+
+    #[test]
+    fn scan_does_not_recurse_dot_directories() {
+        let temp = arrange_directories(&[".hidden/build", ".build"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone())).unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(!backend
+            .is_ignored(&directory.join(".hidden/build"))
+            .unwrap());
+    }
+
+    #[rstest(
+        path,
+        managed,
+        reconcile,
+        expected_ignored,
+        expected_managed,
+        case::unmatched_managed("c", true, true, false, false),
+        case::unmatched_managed_without_reconcile("c", true, false, true, true),
+        case::skipped_managed("a/src/build", true, true, false, false),
+        case::unmatched_foreign("c", false, true, true, false),
+        case::matched_foreign("a/build", false, true, true, true),
+        case::matched_foreign_without_reconcile("a/build", false, false, true, false),
+        case::below_ignored_managed("a/build/c", true, true, false, false),
+        case::below_ignored_managed_without_reconcile("a/build/c", true, false, true, true),
+        case::matched_below_ignored_managed("a/build/target", true, true, true, true),
+        case::below_dot_directory_managed(".hidden/c", true, true, false, false),
+        case::below_ignored_foreign("a/build/c", false, true, true, false)
+    )]
+    fn scan_reconciles_ignored_entries(
+        path: &str,
+        managed: bool,
+        reconcile: bool,
+        expected_ignored: bool,
+        expected_managed: bool,
+    ) {
+        let temp = arrange_directories(&[
+            "a/build/c",
+            "a/build/target",
+            "a/src/build",
+            "c",
+            ".hidden/c",
+        ]);
+        let directory = temp.path().canonicalize().unwrap();
+        let path = directory.join(path);
+        let backend = MemoryBackend::with_foreign_ignores(&[&path]);
+        if managed {
+            backend.manage(&path).unwrap();
+        }
+        // the entries below `a/build` lie within a known ignore
+        if path.parent() == Some(&directory.join("a/build")) {
+            backend.ignore(&directory.join("a/build")).unwrap();
+        }
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone())).unwrap();
+        scanner
+            .scan(directory.clone(), false, false, reconcile)
+            .unwrap();
+
+        assert_eq!(expected_ignored, backend.is_ignored(&path).unwrap());
+        assert_eq!(expected_managed, backend.is_managed(&path).unwrap());
+    }
 
-        recurse
+    #[test]
+    fn unignore_matching_entries() {
+        let temp = arrange_directories(&["a/build", "c"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let backend = MemoryBackend::with_foreign_ignores(&[
+            &directory.join("a/build"),
+            &directory.join("c"),
+        ]);
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone())).unwrap();
+        scanner.unignore_matching(directory.clone(), false).unwrap();
+
+        assert!(!backend.is_ignored(&directory.join("a/build")).unwrap());
+        assert!(backend.is_ignored(&directory.join("c")).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unignore_resolves_paths_through_symlinks() {
+        use crate::ignore_list::IgnoreList;
+        use crate::nextcloud::Nextcloud;
+
+        let temp = arrange_directories(&["root/foo/build"]);
+        let root = temp.path().canonicalize().unwrap().join("root");
+        let link = temp.path().join("link");
+        std::os::unix::fs::symlink(&root, &link).unwrap();
+        let backend = IgnoreList::<Nextcloud>::new(&root);
+        backend.ignore(&root.join("foo/build")).unwrap();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend)).unwrap();
+        scanner
+            .unignore(&[link.join("foo/../foo/build")], false)
+            .unwrap();
+
+        let backend = IgnoreList::<Nextcloud>::new(&root);
+        assert!(!backend.is_ignored(&root.join("foo/build")).unwrap());
+    }
+
+    #[test]
+    fn scan_fails_for_missing_directory() {
+        let temp = arrange_directories(&[]);
+        let directory = temp.path().join("missing");
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(MemoryBackend::default())).unwrap();
+        let result = scanner.scan(directory, false, false, false);
+
+        assert!(matches!(result, Err(Error::Io { .. })));
+    }
+
+    #[test]
+    fn scan_applies_dropignore_files() {
+        let temp = arrange_directories(&[
+            "a/node_modules",
+            "a/b/build",
+            "a/src/node_modules",
+            "c/build",
+        ]);
+        let directory = temp.path().canonicalize().unwrap();
+        fs::write(directory.join("a/.dropignore"), "node_modules\n").unwrap();
+        fs::write(directory.join("a/b/.dropignore"), "!build\n").unwrap();
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone())).unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(backend
+            .is_ignored(&directory.join("a/node_modules"))
+            .unwrap());
+        assert!(backend
+            .is_ignored(&directory.join("a/src/node_modules"))
+            .unwrap());
+        assert!(!backend.is_ignored(&directory.join("a/b/build")).unwrap());
+        assert!(backend.is_ignored(&directory.join("c/build")).unwrap());
+    }
+
+    #[test]
+    fn scan_of_subdirectory_applies_scopes_of_scope_root() {
+        let temp = arrange_directories(&["Clients/acme/out", "Clients/out/acme/out"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let matcher_config = MatcherConfig {
+            scopes: Some(vec![ScopeConfig {
+                path: String::from("Clients"),
+                ignore_specs: Some(vec![MatcherSpec {
+                    pattern: String::from("/acme/out"),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(
+            Matcher::new(&Some(matcher_config)).unwrap(),
+            Box::new(backend.clone()),
+        )
+        .unwrap()
+        .with_scope_root(directory.clone());
+        scanner
+            .scan(directory.join("Clients"), false, false, false)
+            .unwrap();
+
+        assert!(backend
+            .is_ignored(&directory.join("Clients/acme/out"))
+            .unwrap());
+        assert!(!backend
+            .is_ignored(&directory.join("Clients/out/acme/out"))
+            .unwrap());
+    }
+
+    #[test]
+    fn scan_ignores_dropignore_files_above_the_root() {
+        let temp = arrange_directories(&["root/node_modules", "root/a/dist"]);
+        let directory = temp.path().join("root").canonicalize().unwrap();
+        fs::write(temp.path().join(".dropignore"), "node_modules\n").unwrap();
+        fs::write(directory.join(".dropignore"), "dist\n").unwrap();
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone())).unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(!backend.is_ignored(&directory.join("node_modules")).unwrap());
+        assert!(backend.is_ignored(&directory.join("a/dist")).unwrap());
+    }
+
+    #[test]
+    fn scan_applies_git_excludes_within_work_trees() {
+        let temp = arrange_directories(&[
+            "code/repo/.git",
+            "code/repo/dist",
+            "code/repo/src/build",
+            "code/plain/dist",
+        ]);
+        let directory = temp.path().canonicalize().unwrap();
+        fs::write(directory.join("code/repo/.gitignore"), "dist\nbuild\n").unwrap();
+        fs::write(directory.join("code/plain/.gitignore"), "dist\n").unwrap();
+        let backend = MemoryBackend::default();
+
+        let git_excludes = GitExcludes::with_global_excludes(
+            &[directory.join("code")],
+            ignore::gitignore::Gitignore::empty(),
+        );
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone()))
+            .unwrap()
+            .with_git_excludes(git_excludes);
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(backend
+            .is_ignored(&directory.join("code/repo/dist"))
+            .unwrap());
+        // skip-specs still win
+        assert!(!backend
+            .is_ignored(&directory.join("code/repo/src/build"))
+            .unwrap());
+        assert!(!backend
+            .is_ignored(&directory.join("code/plain/dist"))
+            .unwrap());
+    }
+
+    #[cfg(unix)]
+    #[rstest(via_symlink, case::canonical(false), case::symlink(true))]
+    fn scan_anchors_patterns_to_root(via_symlink: bool) {
+        let temp = arrange_directories(&[
+            "dropbox/Projects/foo/out",
+            "dropbox/Archive/Projects/foo/out",
+        ]);
+        let canonical = temp.path().canonicalize().unwrap().join("dropbox");
+        let directory = if via_symlink {
+            let link = temp.path().join("link");
+            std::os::unix::fs::symlink(&canonical, &link).unwrap();
+            link
+        } else {
+            canonical.clone()
+        };
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("/Projects/*/out"),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(
+            Matcher::new(&Some(matcher_config)).unwrap(),
+            Box::new(backend.clone()),
+        )
+        .unwrap();
+        scanner.scan(directory, false, false, false).unwrap();
+
+        assert!(backend
+            .is_ignored(&canonical.join("Projects/foo/out"))
+            .unwrap());
+        assert!(!backend
+            .is_ignored(&canonical.join("Archive/Projects/foo/out"))
+            .unwrap());
+    }
+
+    #[test]
+    fn scan_restricts_specs_to_entry_type() {
+        let temp = arrange_directories(&["a/build", "b"]);
+        let directory = temp.path().canonicalize().unwrap();
+        fs::write(directory.join("b/build"), "").unwrap();
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("build"),
+                entry_type: EntryType::Dir,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(
+            Matcher::new(&Some(matcher_config)).unwrap(),
+            Box::new(backend.clone()),
+        )
+        .unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(backend.is_ignored(&directory.join("a/build")).unwrap());
+        assert!(!backend.is_ignored(&directory.join("b/build")).unwrap());
+    }
+
+    #[test]
+    fn known_file_types_are_not_stat_ed_again() {
+        let temp = arrange_directories(&["a/build"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let file_type = fs::symlink_metadata(directory.join("a/build"))
+            .unwrap()
+            .file_type();
+        fs::remove_dir(directory.join("a/build")).unwrap();
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("build"),
+                entry_type: EntryType::Dir,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let scanner = Scanner::new(
+            Matcher::new(&Some(matcher_config)).unwrap(),
+            Box::new(MemoryBackend::default()),
+        )
+        .unwrap();
+
+        // the entry is gone, so only a stat fails
+        let path = directory.join("a/build");
+        assert!(scanner.matches(&directory, &path, Some(file_type)).unwrap());
+        assert!(scanner.matches(&directory, &path, None).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn scan_matches_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp = arrange_directories(&["src"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let non_utf8 = directory.join(OsStr::from_bytes(b"caf\xe9"));
+        fs::create_dir_all(non_utf8.join("build")).unwrap();
+        fs::write(directory.join(OsStr::from_bytes(b"r\xe9sum\xe9.tmp")), "").unwrap();
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("*.tmp"),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(
+            Matcher::new(&Some(matcher_config)).unwrap(),
+            Box::new(backend.clone()),
+        )
+        .unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(!backend.is_ignored(&non_utf8).unwrap());
+        assert!(backend.is_ignored(&non_utf8.join("build")).unwrap());
+        assert!(backend
+            .is_ignored(&directory.join(OsStr::from_bytes(b"r\xe9sum\xe9.tmp")))
+            .unwrap());
+    }
+
+    #[test]
+    fn scan_applies_marker_conditions() {
+        let temp = arrange_directories(&["rust/target", "design/target"]);
+        let directory = temp.path().canonicalize().unwrap();
+        fs::write(directory.join("rust/Cargo.toml"), "").unwrap();
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("target"),
+                when_sibling: vec![String::from("Cargo.toml")],
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(
+            Matcher::new(&Some(matcher_config)).unwrap(),
+            Box::new(backend.clone()),
+        )
+        .unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(backend.is_ignored(&directory.join("rust/target")).unwrap());
+        assert!(!backend
+            .is_ignored(&directory.join("design/target"))
+            .unwrap());
+    }
+
+    #[test]
+    fn scan_applies_metric_predicates() {
+        let temp = arrange_directories(&[
+            "renders/big",
+            "renders/small",
+            "cache/a",
+            "cache/b",
+            "cache/c",
+            "cache/d",
+            "cache/e",
+        ]);
+        let directory = temp.path().canonicalize().unwrap();
+        fs::write(directory.join("renders/big/out.mp4"), vec![0; 2048]).unwrap();
+        fs::write(directory.join("renders/small/out.mp4"), vec![0; 16]).unwrap();
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("/renders/*"),
+                    min_size: Some(1024),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    min_entries: Some(5),
+                    min_depth: Some(1),
+                    max_depth: Some(1),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+        let backend = MemoryBackend::default();
+
+        let scanner = Scanner::new(
+            Matcher::new(&Some(matcher_config)).unwrap(),
+            Box::new(backend.clone()),
+        )
+        .unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+
+        assert!(backend.is_ignored(&directory.join("renders/big")).unwrap());
+        assert!(!backend
+            .is_ignored(&directory.join("renders/small"))
+            .unwrap());
+        assert!(backend.is_ignored(&directory.join("cache")).unwrap());
+        assert!(!backend.is_ignored(&directory).unwrap());
+    }
+
+    #[test]
+    fn metrics_change_reevaluates_containing_directories() {
+        let temp = arrange_directories(&["renders/shot/frames", "docs"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![MatcherSpec {
+                pattern: String::from("/renders/*"),
+                min_size: Some(1024),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let backend = MemoryBackend::default();
+        let scanner = Scanner::new(
+            Matcher::new(&Some(matcher_config)).unwrap(),
+            Box::new(backend.clone()),
+        )
+        .unwrap();
+        scanner
+            .scan(directory.clone(), false, false, false)
+            .unwrap();
+        assert!(!backend.is_ignored(&directory.join("renders/shot")).unwrap());
+
+        let path = directory.join("renders/shot/frames/0001.exr");
+        fs::write(&path, vec![0; 2048]).unwrap();
+        scanner.metrics.invalidate(&path);
+        let mut scanner_stats = ScannerStats::default();
+        scanner.handle_metrics_change(&directory, &path, false, false, &mut scanner_stats);
+
+        assert!(backend.is_ignored(&directory.join("renders/shot")).unwrap());
+        assert!(!backend
+            .is_ignored(&directory.join("renders/shot/frames"))
+            .unwrap());
+        assert_eq!(1, scanner_stats.new_ignores);
+    }
+
+    #[rstest(
+        unignore,
+        dry_run,
+        expected_src_build_ignored,
+        expected_c_ignored,
+        case::report_only(vec![], false, true, true),
+        case::all(vec![""], false, false, false),
+        case::all_with_dry_run(vec![""], true, true, true),
+        case::group(vec!["a/src"], false, false, true),
+        case::orphan(vec!["c"], false, true, false),
+        case::several(vec!["a/src/build", "c"], false, false, false)
+    )]
+    fn orphans_are_unignored(
+        unignore: Vec<&str>,
+        dry_run: bool,
+        expected_src_build_ignored: bool,
+        expected_c_ignored: bool,
+    ) {
+        let temp = arrange_directories(&["a/build", "a/src/build", "c"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let backend = MemoryBackend::with_foreign_ignores(&[
+            &directory.join("a/build"),
+            &directory.join("a/src/build"),
+            &directory.join("c"),
+        ]);
+
+        let unignore: Vec<PathBuf> = unignore.iter().map(|p| directory.join(p)).collect();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(backend.clone())).unwrap();
+        scanner
+            .orphans(directory.clone(), &unignore, dry_run)
+            .unwrap();
+
+        assert!(backend.is_ignored(&directory.join("a/build")).unwrap());
+        assert_eq!(
+            expected_src_build_ignored,
+            backend.is_ignored(&directory.join("a/src/build")).unwrap()
+        );
+        assert_eq!(
+            expected_c_ignored,
+            backend.is_ignored(&directory.join("c")).unwrap()
+        );
+    }
+
+    #[test]
+    fn repair_with_dry_run_reports_nested_entries() {
+        let temp = arrange_directories(&["a/b/c", "d/e"]);
+        let directory = temp.path().canonicalize().unwrap();
+        let states = [
+            (directory.join("a"), IgnoreState::Invalid),
+            (directory.join("a/b"), IgnoreState::Invalid),
+            (directory.join("a/b/c"), IgnoreState::Partial),
+            (directory.join("d"), IgnoreState::Partial),
+            (directory.join("d/e"), IgnoreState::Invalid),
+        ];
+        let states: Vec<(&Path, IgnoreState)> =
+            states.iter().map(|(p, s)| (p.as_path(), *s)).collect();
+
+        let repairs = |dry_run| {
+            let backend = MemoryBackend::with_inconsistent_states(&states);
+            let scanner = Scanner::new(arrange_matcher(), Box::new(backend)).unwrap();
+            let mut scanner_stats = ScannerStats::default();
+            Scanner::walk(&directory, &mut scanner_stats, |path, _, stats| {
+                scanner.handle_repair_entry(path, dry_run, stats)
+            });
+            scanner_stats.repairs
+        };
+
+        // `d` gets ignored, so `d/e` isn't synced anymore and is left as it is
+        assert_eq!(4, repairs(true));
+        assert_eq!(4, repairs(false));
+    }
+
+    #[test]
+    fn conflicts_are_deleted_or_merged() {
+        let temp = arrange_directories(&[
+            "a/build (Selective Sync Conflict)/classes",
+            "a/src (Selective Sync Conflict)/main",
+            "a/src/test",
+        ]);
+        let directory = temp.path().canonicalize().unwrap();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(MemoryBackend::default())).unwrap();
+        scanner
+            .conflicts(directory.clone(), true, true, false)
+            .unwrap();
+
+        assert!(!directory.join("a/build (Selective Sync Conflict)").exists());
+        assert!(!directory.join("a/src (Selective Sync Conflict)").exists());
+        assert!(directory.join("a/src/main").exists());
+        assert!(directory.join("a/src/test").exists());
+    }
+
+    #[test]
+    fn conflicts_with_dry_run_changes_nothing() {
+        let temp = arrange_directories(&[
+            "a/build (Selective Sync Conflict)",
+            "a/src (Selective Sync Conflict)/main",
+        ]);
+        let directory = temp.path().canonicalize().unwrap();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(MemoryBackend::default())).unwrap();
+        scanner
+            .conflicts(directory.clone(), true, true, true)
+            .unwrap();
+
+        assert!(directory.join("a/build (Selective Sync Conflict)").exists());
+        assert!(directory
+            .join("a/src (Selective Sync Conflict)/main")
+            .exists());
+        assert!(!directory.join("a/src").exists());
+    }
+
+    #[rstest(
+        delete,
+        merge,
+        expected_deleted,
+        expected_merged,
+        case::report_only(false, false, false, false),
+        case::delete(true, false, true, false),
+        case::merge(false, true, false, true)
+    )]
+    fn conflicts_are_only_resolved_as_requested(
+        delete: bool,
+        merge: bool,
+        expected_deleted: bool,
+        expected_merged: bool,
+    ) {
+        let temp = arrange_directories(&[
+            "a/build (Selective Sync Conflict)",
+            "a/src (Selective Sync Conflict)/main",
+        ]);
+        let directory = temp.path().canonicalize().unwrap();
+
+        let scanner = Scanner::new(arrange_matcher(), Box::new(MemoryBackend::default())).unwrap();
+        scanner
+            .conflicts(directory.clone(), delete, merge, false)
+            .unwrap();
+
+        assert_eq!(
+            expected_deleted,
+            !directory.join("a/build (Selective Sync Conflict)").exists()
+        );
+        assert_eq!(
+            expected_merged,
+            !directory.join("a/src (Selective Sync Conflict)").exists()
+        );
+        assert_eq!(expected_merged, directory.join("a/src/main").exists());
+    }
+
+    fn arrange_matcher() -> Matcher {
+        let matcher_config = MatcherConfig {
+            ignore_specs: Some(vec![
+                MatcherSpec {
+                    pattern: String::from("**/build"),
+                    ..Default::default()
+                },
+                MatcherSpec {
+                    pattern: String::from("**/target"),
+                    ..Default::default()
+                },
+            ]),
+            skip_specs: Some(vec![MatcherSpec {
+                pattern: String::from("**/src/build"),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        Matcher::new(&Some(matcher_config)).unwrap()
+    }
+
+    fn arrange_directories(paths: &[&str]) -> TempDir {
+        // no extended file system attributes needed, so tmpfs is fine
+        let temp = Builder::new()
+            .prefix("dropignore-testing")
+            .tempdir()
+            .unwrap();
+        for path in paths {
+            fs::create_dir_all(temp.path().join(path)).unwrap();
+        }
+        temp
     }
 }
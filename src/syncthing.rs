@@ -0,0 +1,37 @@
+use crate::ignore_list::IgnoreListFormat;
+
+/// The `.stignore` file of a Syncthing folder.
+pub struct Syncthing;
+
+impl IgnoreListFormat for Syncthing {
+    const FILE_NAME: &'static str = ".stignore";
+
+    fn pattern(relative_path: &str) -> String {
+        let mut pattern = String::from("/");
+        for c in relative_path.chars() {
+            // Syncthing doesn't support escaping on Windows (`\` is the path separator there)
+            if !cfg!(windows) && matches!(c, '\\' | '*' | '?' | '[' | ']' | '{' | '}') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+
+        pattern
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern() {
+        assert_eq!("/foo/build", Syncthing::pattern("foo/build"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn pattern_with_glob_characters() {
+        assert_eq!("/foo/\\{a\\}\\*", Syncthing::pattern("foo/{a}*"));
+    }
+}